@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+
+use eframe::{get_value, set_value, Storage, APP_KEY};
+
+use crate::Datos;
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Almacén                                                                             +++
+// +----------------------------------------------------------------------------------------------+
+
+// El mismo `app.ron` que usa eframe para guardar `Datos`, para poder leerlo y escribirlo sin
+// abrir la ventana. Se escribe igual que eframe: un mapa clave/valor en RON.
+
+pub struct Almacen {
+    ruta: PathBuf,
+    kv: HashMap<String, String>,
+}
+
+impl Almacen {
+    pub fn abrir() -> io::Result<Self> {
+        let dir =
+            crate::data_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "data_dir"))?;
+        std::fs::create_dir_all(&dir)?;
+
+        let ruta = dir.join("app.ron");
+        let kv = match std::fs::read_to_string(&ruta) {
+            Ok(texto) => ron::from_str(&texto)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self { ruta, kv })
+    }
+
+    /// Los datos guardados, completados igual que al abrir la ventana.
+    pub fn datos(&self) -> Datos {
+        let mut datos: Datos = get_value(self, APP_KEY).unwrap_or_default();
+        datos.lunes.get_or_insert_with(crate::lunes_actual);
+        datos
+    }
+
+    pub fn guardar(&mut self, datos: &Datos) -> io::Result<()> {
+        set_value(self, APP_KEY, datos);
+
+        let texto = ron::ser::to_string_pretty(&self.kv, Default::default())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        std::fs::write(&self.ruta, texto)
+    }
+}
+
+impl Storage for Almacen {
+    fn get_string(&self, key: &str) -> Option<String> {
+        self.kv.get(key).cloned()
+    }
+
+    fn set_string(&mut self, key: &str, value: String) {
+        self.kv.insert(key.to_owned(), value);
+    }
+
+    fn flush(&mut self) {}
+}
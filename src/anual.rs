@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::calculo::{self, Ausencia};
+use crate::historial;
+use crate::Datos;
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Cómputo Anual                                                                       +++
+// +----------------------------------------------------------------------------------------------+
+
+// Horas del año según el convenio, repartidas entre los días laborables en proporción a la jornada
+// de cada semana: una semana de jornada intensiva pesa menos. Los festivos y las demás ausencias
+// no son laborables, y las vacaciones que faltan por marcar se descuentan con la jornada actual.
+// Se cuenta desde la primera semana con fichajes, por si se empezó a mitad de año.
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Config {
+    /// Horas de trabajo efectivo al año; 0 = sin cómputo anual.
+    pub horas: u32,
+    /// Días laborables de vacaciones al año, marcados o no.
+    pub vacaciones: u32,
+}
+
+/// Cómputo del año, en segundos.
+#[derive(Clone, Debug, Default)]
+pub struct Computo {
+    pub ejercicio: i32,
+    /// Horas del año del convenio.
+    pub objetivo: i64,
+    /// Objetivo proporcional hasta hoy incluido.
+    pub a_hoy: i64,
+    /// Trabajado hasta hoy, sin las extraordinarias.
+    pub trabajado: i64,
+    pub laborables: u32,
+    /// Laborables que quedan después de hoy.
+    pub quedan: u32,
+    /// Saldo a fin de año si el resto se trabaja la jornada configurada.
+    pub previsto: i64,
+    /// Saldo a fin de año si se sigue al ritmo de hasta hoy.
+    pub ritmo: i64,
+}
+
+impl Computo {
+    pub fn saldo(&self) -> i64 {
+        self.trabajado - self.a_hoy
+    }
+}
+
+pub fn calcular(datos: &Datos, hoy: NaiveDate) -> Computo {
+    let ejercicio = hoy.year();
+    let semanas = historial::semanas(datos);
+    let mut computo = Computo {
+        ejercicio,
+        objetivo: i64::from(datos.anual.horas) * 3600,
+        ..Default::default()
+    };
+
+    let (Some(enero), Some(diciembre)) = (
+        NaiveDate::from_ymd_opt(ejercicio, 1, 1),
+        NaiveDate::from_ymd_opt(ejercicio, 12, 31),
+    ) else {
+        return computo;
+    };
+    let inicio = semanas
+        .first()
+        .map_or(enero, |s| s.lunes.max(enero))
+        .min(hoy);
+
+    // Jornada diaria de cada semana; las que no tienen semana, la de ahora.
+    let actual = calculo::segundos(&datos.config[0].cell);
+    let jornadas: BTreeMap<NaiveDate, i64> = semanas
+        .iter()
+        .map(|s| (s.lunes, calculo::segundos(&s.config[0].cell)))
+        .collect();
+
+    let mut total = 0;
+    let mut periodo = 0;
+    let mut transcurrido = 0;
+    let mut futuro = 0;
+    let mut marcadas = 0;
+    let mut fecha = enero;
+    while fecha <= diciembre {
+        let dia = fecha.weekday().num_days_from_monday();
+        let ausencia = datos.ausencias.get(&fecha);
+        if ausencia == Some(&Ausencia::Vacaciones) {
+            marcadas += 1;
+        }
+        if dia < 5 && ausencia.is_none() {
+            let lunes = fecha - Duration::days(i64::from(dia));
+            let peso = jornadas.get(&lunes).copied().unwrap_or(actual);
+            total += peso;
+            computo.laborables += 1;
+            if fecha >= inicio {
+                periodo += peso;
+                if fecha <= hoy {
+                    transcurrido += peso;
+                }
+            }
+            if fecha > hoy {
+                futuro += peso;
+                computo.quedan += 1;
+            }
+        }
+        fecha += Duration::days(1);
+    }
+
+    // Vacaciones sin marcar: se tomarán más adelante.
+    let pendientes = datos.anual.vacaciones.saturating_sub(marcadas);
+    let pendientes = pendientes.min(computo.quedan);
+    let vacaciones = i64::from(pendientes) * actual;
+    total -= vacaciones;
+    periodo -= vacaciones;
+    futuro -= vacaciones;
+    computo.laborables -= pendientes;
+    computo.quedan -= pendientes;
+
+    if total <= 0 || transcurrido <= 0 {
+        return computo;
+    }
+
+    // Lo fichado de inicio a hoy: un día sin fichajes no se ha trabajado, y de una semana a medias
+    // sólo se quitan las extraordinarias de sus días.
+    for semana in &semanas {
+        computo.trabajado += (0..5)
+            .filter(|&dia| inicio <= semana.fecha(dia) && semana.fecha(dia) <= hoy)
+            .map(|dia| semana.trabajado(dia))
+            .sum::<i64>()
+            - semana.extras_entre(inicio, hoy);
+    }
+
+    let objetivo_periodo = computo.objetivo * periodo / total;
+    computo.a_hoy = computo.objetivo * transcurrido / total;
+    computo.previsto = computo.trabajado + futuro - objetivo_periodo;
+    computo.ritmo = computo.saldo() * periodo / transcurrido;
+
+    computo
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const H: i64 = 3600;
+
+    fn fecha(mes: u32, dia: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, mes, dia).unwrap()
+    }
+
+    #[test]
+    fn trabajado_es_lo_fichado_hasta_hoy_sin_extraordinarias() {
+        let mut datos = Datos {
+            lunes: Some(fecha(3, 9)),
+            ..Datos::default()
+        };
+        datos.anual.horas = 1700;
+        datos.reglas.extras.diaria = 8 * 60;
+        // Lunes con media hora extraordinaria, martes sin fichar y el jueves ya importado.
+        for (dia, salida) in [(0, "1600"), (2, "1500"), (3, "1000")] {
+            datos.fichajes[dia].cell = "      0730".to_owned();
+            datos.fichajes[dia + 5].cell = format!("      {salida}");
+        }
+
+        let computo = calcular(&datos, fecha(3, 11));
+        assert_eq!(computo.trabajado, 15 * H + 30 * 60);
+        assert_eq!(computo.laborables, 261);
+        assert_eq!(computo.quedan, 211);
+    }
+}
@@ -0,0 +1,607 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime};
+use eframe::egui::Context;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::almacen::Almacen;
+use crate::auditoria::{self, Entrada, Motivo};
+use crate::estado;
+use crate::{barra, calculo, cli, copia, csv, historial, ics, instancia, webhook};
+use crate::{Datos, DAYS_WEEK_NAMES, FICHAJE_NAMES, HM};
+
+// +----------------------------------------------------------------------------------------------+
+// +++      API HTTP Local                                                                      +++
+// +----------------------------------------------------------------------------------------------+
+
+// HTTP/1.1 mínimo escrito a mano, sólo en 127.0.0.1 y con `Authorization: Bearer <token>` en
+// todas las peticiones. Las fechas van en ISO (AAAA-MM-DD) y los tiempos en segundos:
+//
+//   GET  /status                          estado de hoy y saldo de la semana
+//   POST /punch           {"hora"?}       ficha ahora (o a "HHMM") en la primera casilla libre
+//   GET  /days?desde&hasta                fichajes, total y objetivo (semana en curso)
+//   PUT  /days/FECHA/N    {"hora"}        cambia el fichaje N (1..4), sólo en la semana en curso
+//   GET  /export?formato&desde&hasta      csv, ics, json o pdf (mes en curso)
+//
+// Lo atiende la ventana, dueña de los datos, o `horario api` si no está abierta.
+
+/// Preferencias de la API, en Configurar > API.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub activa: bool,
+    pub puerto: u16,
+    pub token: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            activa: false,
+            puerto: 5878,
+            token: String::new(),
+        }
+    }
+}
+
+/// Token nuevo: 32 bytes aleatorios en hexadecimal.
+pub fn token() -> io::Result<String> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).map_err(io::Error::other)?;
+    Ok(auditoria::hex(&bytes))
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Peticion {
+    pub metodo: String,
+    pub ruta: Vec<String>,
+    pub consulta: HashMap<String, String>,
+    pub cuerpo: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Respuesta {
+    pub estado: u16,
+    pub tipo: &'static str,
+    pub cuerpo: Vec<u8>,
+}
+
+impl Respuesta {
+    fn json(estado: u16, valor: &Value) -> Self {
+        Self {
+            estado,
+            tipo: "application/json",
+            cuerpo: valor.to_string().into_bytes(),
+        }
+    }
+
+    fn error(estado: u16, mensaje: &str) -> Self {
+        Self::json(estado, &json!({ "error": mensaje }))
+    }
+}
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Rutas                                                                               +++
+// +----------------------------------------------------------------------------------------------+
+
+fn fecha(texto: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(texto, "%Y-%m-%d")
+        .map_err(|_| format!("Fecha no v\u{e1}lida: {texto} (AAAA-MM-DD)"))
+}
+
+fn rango(
+    peticion: &Peticion,
+    desde: NaiveDate,
+    hasta: NaiveDate,
+) -> Result<(NaiveDate, NaiveDate), String> {
+    let leer = |clave: &str, defecto| {
+        peticion
+            .consulta
+            .get(clave)
+            .map_or(Ok(defecto), |f| fecha(f))
+    };
+    Ok((leer("desde", desde)?, leer("hasta", hasta)?))
+}
+
+/// `"hora"` del cuerpo JSON, en `HHMM`; vacía si no viene.
+fn hora(cuerpo: &str) -> Result<String, String> {
+    if cuerpo.trim().is_empty() {
+        return Ok(String::new());
+    }
+    let valor: Value = serde_json::from_str(cuerpo).map_err(|err| err.to_string())?;
+    match valor
+        .get("hora")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+    {
+        "" => Ok(String::new()),
+        hora => crate::cli::hhmm(hora),
+    }
+}
+
+fn status(datos: &Datos, ahora: NaiveDateTime) -> Value {
+    let estado = estado::estado(datos, ahora);
+    json!({
+        "dentro": estado.dentro,
+        "hoy": estado.hoy,
+        "saldo": estado.saldo,
+        "salida": estado.salida.map(|s| s.format("%H:%M").to_string()),
+        "texto": barra::generar(&estado, barra::Formato::Texto),
+    })
+}
+
+fn dias(datos: &Datos, desde: NaiveDate, hasta: NaiveDate) -> Value {
+    let mut dias = Vec::new();
+    for semana in historial::semanas(datos) {
+        let resumen = semana.resumen();
+        for (dia, nombre) in DAYS_WEEK_NAMES.iter().enumerate().take(5) {
+            let fecha = semana.fecha(dia);
+            if fecha < desde || fecha > hasta {
+                continue;
+            }
+            dias.push(json!({
+                "fecha": fecha.to_string(),
+                "dia": nombre,
+                "fichajes": (0..4)
+                    .map(|fila| calculo::hora(&semana.fichajes[dia + fila * 5].cell))
+                    .collect::<Vec<_>>(),
+                "total": resumen.dias[dia],
+                "objetivo": resumen.objetivos[dia],
+                "ausencia": semana.ausencia(dia),
+            }));
+        }
+    }
+    Value::Array(dias)
+}
+
+fn exportar(datos: &Datos, peticion: &Peticion, hoy: NaiveDate) -> Result<Respuesta, String> {
+    let (desde, hasta) = rango(peticion, hoy.with_day(1).unwrap_or(hoy), hoy)?;
+    let texto = |tipo, cuerpo: String| Respuesta {
+        estado: 200,
+        tipo,
+        cuerpo: cuerpo.into_bytes(),
+    };
+
+    Ok(
+        match peticion
+            .consulta
+            .get("formato")
+            .map_or("csv", String::as_str)
+        {
+            "csv" => texto("text/csv; charset=utf-8", csv::generar(datos, desde, hasta)),
+            "ics" => texto(
+                "text/calendar; charset=utf-8",
+                ics::generar(datos, desde, hasta, true),
+            ),
+            "json" => texto(
+                "application/json",
+                copia::generar(datos).map_err(|err| err.to_string())?,
+            ),
+            "pdf" => Respuesta {
+                estado: 200,
+                tipo: "application/pdf",
+                cuerpo: crate::pdf::informe(datos, desde),
+            },
+            formato => return Err(format!("Formato desconocido: {formato}")),
+        },
+    )
+}
+
+/// Atiende una petición sobre `datos` y devuelve la respuesta y los cambios para la auditoría.
+pub fn atender(datos: &mut Datos, peticion: &Peticion) -> (Respuesta, Vec<Entrada>) {
+    let ahora = Local::now().naive_local();
+    let lunes = datos.lunes.unwrap_or_else(crate::lunes_actual);
+    let ruta: Vec<&str> = peticion.ruta.iter().map(String::as_str).collect();
+    let conflicto = |err: String| (Respuesta::error(409, &err), Vec::new());
+
+    match (peticion.metodo.as_str(), ruta.as_slice()) {
+        ("GET", ["status"]) => (Respuesta::json(200, &status(datos, ahora)), Vec::new()),
+        ("POST", ["punch"]) => {
+            let momento = match hora(&peticion.cuerpo) {
+                Ok(hora) if hora.is_empty() => ahora,
+                Ok(hora) => ahora
+                    .date()
+                    .and_time(NaiveTime::parse_from_str(&hora, HM).unwrap_or_default()),
+                Err(err) => return (Respuesta::error(400, &err), Vec::new()),
+            };
+            match estado::fichar(datos, momento, Motivo::Api) {
+                Ok(entrada) => (
+                    Respuesta::json(
+                        200,
+                        &json!({
+                            "fichaje": FICHAJE_NAMES[entrada.celda / 5],
+                            "hora": calculo::hora(&entrada.nuevo),
+                            "estado": status(datos, ahora),
+                        }),
+                    ),
+                    vec![entrada],
+                ),
+                Err(err) => conflicto(err),
+            }
+        }
+        ("GET", ["days"]) => match rango(peticion, lunes, lunes + chrono::Duration::days(4)) {
+            Ok((desde, hasta)) => (Respuesta::json(200, &dias(datos, desde, hasta)), Vec::new()),
+            Err(err) => (Respuesta::error(400, &err), Vec::new()),
+        },
+        ("PUT", ["days", dia, fichaje]) => {
+            let (fecha, fila, hora) =
+                match (fecha(dia), fichaje.parse::<usize>(), hora(&peticion.cuerpo)) {
+                    (Ok(fecha), Ok(n @ 1..=4), Ok(hora)) => (fecha, n - 1, hora),
+                    (Err(err), _, _) | (_, _, Err(err)) => {
+                        return (Respuesta::error(400, &err), Vec::new())
+                    }
+                    _ => {
+                        return (
+                            Respuesta::error(400, "Fichaje no v\u{e1}lido (1..4)"),
+                            Vec::new(),
+                        )
+                    }
+                };
+            let dia = (fecha - lunes).num_days();
+            if !(0..5).contains(&dia) {
+                return conflicto(format!(
+                    "S\u{f3}lo se pueden cambiar los d\u{ed}as de la semana del {}",
+                    lunes.format(crate::DMY)
+                ));
+            }
+            match estado::poner(datos, dia as usize + fila * 5, &hora, Motivo::Api) {
+                Ok(entrada) => (
+                    Respuesta::json(200, &dias(datos, fecha, fecha)[0]),
+                    vec![entrada],
+                ),
+                Err(err) => conflicto(err),
+            }
+        }
+        ("GET", ["export"]) => match exportar(datos, peticion, ahora.date()) {
+            Ok(respuesta) => (respuesta, Vec::new()),
+            Err(err) => (Respuesta::error(400, &err), Vec::new()),
+        },
+        (_, ["status" | "punch" | "days" | "export", ..]) => (
+            Respuesta::error(405, "M\u{e9}todo no permitido"),
+            Vec::new(),
+        ),
+        _ => (Respuesta::error(404, "No existe"), Vec::new()),
+    }
+}
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Servidor                                                                            +++
+// +----------------------------------------------------------------------------------------------+
+
+/// Compara sin salir en el primer byte distinto.
+fn mismo_token(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |d, (x, y)| d | (x ^ y)) == 0
+}
+
+/// Tiempo para leer la petición entera: un cliente que mande byte a byte no bloquea la API.
+const PLAZO: Duration = Duration::from_secs(5);
+
+/// La conexión con un plazo para toda la lectura, no para cada `read`.
+struct ConPlazo<'a> {
+    conexion: &'a TcpStream,
+    limite: Instant,
+}
+
+impl Read for ConPlazo<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let queda = self.limite.saturating_duration_since(Instant::now());
+        if queda.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.conexion.set_read_timeout(Some(queda))?;
+        self.conexion.read(buf)
+    }
+}
+
+/// Deshace el `%XX` y el `+` de un valor de la consulta.
+fn decodificar(valor: &str) -> String {
+    let mut bytes = Vec::with_capacity(valor.len());
+    let mut resto = valor.as_bytes();
+    while let Some((&byte, siguiente)) = resto.split_first() {
+        resto = siguiente;
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => match std::str::from_utf8(resto.get(..2).unwrap_or_default())
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(decodificado) => {
+                    bytes.push(decodificado);
+                    resto = &resto[2..];
+                }
+                None => bytes.push(b'%'),
+            },
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Lee una petición; `Err` con la respuesta si está mal formada, sin token válido o no llega a
+/// tiempo.
+fn leer(conexion: &TcpStream, token: &str) -> Result<Peticion, Respuesta> {
+    let mut lector = BufReader::new(ConPlazo {
+        conexion,
+        limite: Instant::now() + PLAZO,
+    });
+    let mut linea = String::new();
+    lector
+        .read_line(&mut linea)
+        .map_err(|_| Respuesta::error(400, "Petici\u{f3}n no v\u{e1}lida"))?;
+
+    let mut partes = linea.split_whitespace();
+    let metodo = partes.next().unwrap_or_default().to_owned();
+    let objetivo = partes.next().unwrap_or_default();
+    let (ruta, consulta) = objetivo.split_once('?').unwrap_or((objetivo, ""));
+
+    let mut cabeceras = HashMap::new();
+    loop {
+        let mut linea = String::new();
+        match lector.read_line(&mut linea) {
+            Ok(0) => break,
+            Ok(_) if linea.trim().is_empty() => break,
+            Ok(_) if cabeceras.len() < 64 => {
+                if let Some((clave, valor)) = linea.split_once(':') {
+                    cabeceras.insert(clave.trim().to_lowercase(), valor.trim().to_owned());
+                }
+            }
+            _ => return Err(Respuesta::error(400, "Petici\u{f3}n no v\u{e1}lida")),
+        }
+    }
+
+    let autorizado = cabeceras
+        .get("authorization")
+        .and_then(|valor| valor.strip_prefix("Bearer "))
+        .is_some_and(|valor| !token.is_empty() && mismo_token(valor.trim(), token));
+    if !autorizado {
+        return Err(Respuesta::error(401, "Token no v\u{e1}lido"));
+    }
+
+    let largo: usize = cabeceras
+        .get("content-length")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(0);
+    if largo > 64 * 1024 {
+        return Err(Respuesta::error(413, "Cuerpo demasiado grande"));
+    }
+    let mut cuerpo = vec![0; largo];
+    lector
+        .read_exact(&mut cuerpo)
+        .map_err(|_| Respuesta::error(400, "Cuerpo incompleto"))?;
+
+    Ok(Peticion {
+        metodo,
+        ruta: ruta
+            .split('/')
+            .filter(|p| !p.is_empty())
+            .map(str::to_owned)
+            .collect(),
+        consulta: consulta
+            .split('&')
+            .filter_map(|p| p.split_once('='))
+            .map(|(k, v)| (decodificar(k), decodificar(v)))
+            .collect(),
+        cuerpo: String::from_utf8_lossy(&cuerpo).into_owned(),
+    })
+}
+
+fn escribir(mut conexion: &TcpStream, respuesta: &Respuesta) {
+    let razon = match respuesta.estado {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        _ => "Service Unavailable",
+    };
+    let _ = write!(
+        conexion,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        respuesta.estado,
+        razon,
+        respuesta.tipo,
+        respuesta.cuerpo.len()
+    );
+    let _ = conexion.write_all(&respuesta.cuerpo);
+}
+
+/// Atiende las conexiones de una en una hasta que se levante `parar`.
+fn servir(
+    socket: &TcpListener,
+    token: &str,
+    parar: &AtomicBool,
+    mut atender: impl FnMut(&Peticion) -> Respuesta,
+) {
+    for conexion in socket.incoming() {
+        if parar.load(Ordering::Relaxed) {
+            return;
+        }
+        let Ok(conexion) = conexion else {
+            continue;
+        };
+        let _ = conexion.set_write_timeout(Some(PLAZO));
+
+        let respuesta = match leer(&conexion, token) {
+            Ok(peticion) => atender(&peticion),
+            Err(respuesta) => respuesta,
+        };
+        escribir(&conexion, &respuesta);
+    }
+}
+
+/// Servidor de la ventana: pasa cada petición a `update` y espera la respuesta.
+pub struct Servidor {
+    puerto: u16,
+    parar: Arc<AtomicBool>,
+    peticiones: Receiver<(Peticion, Sender<Respuesta>)>,
+    hilo: Option<JoinHandle<()>>,
+}
+
+impl std::fmt::Debug for Servidor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Servidor")
+            .field("puerto", &self.puerto)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Servidor {
+    pub fn iniciar(config: &Config, ctx: &Context) -> io::Result<Self> {
+        let socket = TcpListener::bind((Ipv4Addr::LOCALHOST, config.puerto))?;
+        let parar = Arc::new(AtomicBool::new(false));
+        let (enviar, peticiones) = mpsc::channel();
+
+        let token = config.token.clone();
+        let ctx = ctx.clone();
+        let hilo = Arc::clone(&parar);
+        let hilo = std::thread::spawn(move || {
+            servir(&socket, &token, &hilo, |peticion| {
+                let (responder, respuesta) = mpsc::channel();
+                if enviar.send((peticion.clone(), responder)).is_err() {
+                    return Respuesta::error(503, "Cerrando");
+                }
+                ctx.request_repaint();
+                respuesta
+                    .recv_timeout(Duration::from_secs(10))
+                    .unwrap_or_else(|_| Respuesta::error(503, "La ventana no responde"))
+            });
+        });
+
+        Ok(Self {
+            puerto: config.puerto,
+            parar,
+            peticiones,
+            hilo: Some(hilo),
+        })
+    }
+
+    pub fn peticiones(&self) -> Vec<(Peticion, Sender<Respuesta>)> {
+        self.peticiones.try_iter().collect()
+    }
+}
+
+impl Drop for Servidor {
+    fn drop(&mut self) {
+        // Una petición a medio atender ya no tendrá respuesta de la ventana.
+        let (_, peticiones) = mpsc::channel();
+        drop(std::mem::replace(&mut self.peticiones, peticiones));
+
+        // `incoming` no vuelve hasta la siguiente conexión: se hace una para que vea `parar`.
+        // Se espera a que el hilo suelte el puerto, por si se vuelve a abrir enseguida.
+        self.parar.store(true, Ordering::Relaxed);
+        let _ = TcpStream::connect((Ipv4Addr::LOCALHOST, self.puerto));
+        if let Some(hilo) = self.hilo.take() {
+            let _ = hilo.join();
+        }
+    }
+}
+
+/// `horario api`: sirve la API sin ventana, leyendo y guardando `app.ron` en cada petición. Las
+/// órdenes del terminal llegan por el socket de `instancia`, como si hubiera ventana.
+pub fn sin_ventana() -> Result<(), String> {
+    let cerrojo = instancia::cerrojo()
+        .map_err(|err| err.to_string())?
+        .ok_or("La ventana de Horario est\u{e1} abierta: active la API en Configurar")?;
+
+    let leer_datos = || Almacen::abrir().map(|almacen| (almacen.datos(), almacen));
+    let (mut datos, mut almacen) = leer_datos().map_err(|err| err.to_string())?;
+    if datos.api.token.is_empty() {
+        datos.api.token = token().map_err(|err| err.to_string())?;
+        almacen.guardar(&datos).map_err(|err| err.to_string())?;
+    }
+
+    let socket = TcpListener::bind((Ipv4Addr::LOCALHOST, datos.api.puerto))
+        .map_err(|err| format!("Puerto {}: {err}", datos.api.puerto))?;
+    println!("http://127.0.0.1:{}", datos.api.puerto);
+    println!("Authorization: Bearer {}", datos.api.token);
+
+    // Las peticiones HTTP y las del terminal no pueden guardar a la vez.
+    let ocupado = Arc::new(Mutex::new(()));
+    let hilo = Arc::clone(&ocupado);
+    let token = datos.api.token;
+    let http = std::thread::spawn(move || {
+        let enviador = webhook::Enviador::iniciar();
+        servir(&socket, &token, &AtomicBool::new(false), |peticion| {
+            let _ocupado = hilo.lock().unwrap_or_else(PoisonError::into_inner);
+            let Ok((mut datos, mut almacen)) = leer_datos() else {
+                return Respuesta::error(503, "No se pueden leer los datos");
+            };
+            let (respuesta, entradas) = atender(&mut datos, peticion);
+            if !entradas.is_empty()
+                && (almacen.guardar(&datos).is_err() || auditoria::registrar(&entradas).is_err())
+            {
+                return Respuesta::error(503, "No se pueden guardar los datos");
+            }
+            let ahora = chrono::Local::now().naive_local();
+            enviador.avisar(&datos, &webhook::fichajes(&entradas, ahora.date()), ahora);
+            respuesta
+        });
+    });
+
+    let instancia = instancia::Instancia::escuchar(cerrojo, || ());
+    while let Some(peticion) = instancia.esperar() {
+        let resultado = match peticion.args.first().map(String::as_str) {
+            Some("show" | "mostrar") => {
+                Err("Horario est\u{e1} sirviendo la API sin ventana".to_owned())
+            }
+            _ => {
+                let _ocupado = ocupado.lock().unwrap_or_else(PoisonError::into_inner);
+                cli::en_almacen(&peticion.args, true)
+            }
+        };
+        peticion.responder(resultado);
+    }
+
+    // Sin socket se sigue sirviendo la API, con el cerrojo.
+    let _ = http.join();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodificar_los_valores_de_la_consulta() {
+        assert_eq!(decodificar("2026-10-12"), "2026-10-12");
+        assert_eq!(
+            decodificar("m%C3%A9dico+ma%c3%b1ana"),
+            "m\u{e9}dico ma\u{f1}ana"
+        );
+        assert_eq!(decodificar("100%"), "100%");
+        assert_eq!(decodificar("%zz%4"), "%zz%4");
+    }
+
+    #[test]
+    fn leer_corta_al_cliente_que_no_termina() {
+        let socket = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut cliente = TcpStream::connect(socket.local_addr().unwrap()).unwrap();
+        let (conexion, _) = socket.accept().unwrap();
+
+        // Un byte de vez en cuando no alarga el plazo de la petición.
+        let goteo = std::thread::spawn(move || {
+            for byte in b"GET /status HTTP/1.1\r\n" {
+                if cliente.write_all(&[*byte]).is_err() {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(400));
+            }
+        });
+
+        let inicio = Instant::now();
+        assert!(leer(&conexion, "token").is_err());
+        assert!(inicio.elapsed() < PLAZO + Duration::from_secs(1));
+        drop(conexion);
+        goteo.join().unwrap();
+    }
+}
@@ -0,0 +1,350 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::Cell;
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Registro de Fichajes                                                                +++
+// +----------------------------------------------------------------------------------------------+
+
+// Registro de jornada (RD-ley 8/2019): cada alta, cambio o borrado de un fichaje se añade al
+// final de `auditoria.jsonl`, una entrada JSON por línea. El fichero nunca se reescribe.
+//
+// Cada entrada lleva el SHA-256 de su contenido encadenado con el hash de la entrada anterior,
+// de forma que modificar o borrar una entrada rompe todos los enlaces posteriores.
+
+const FICHERO: &str = "auditoria.jsonl";
+
+/// Fichero, largo tras la última escritura y hash de su última entrada, para no releer el
+/// registro en cada cambio. Si el largo ya no es el mismo, otro proceso (el terminal, la API) ha
+/// escrito después y se vuelve a leer el final del fichero.
+static ULTIMO: Mutex<Option<(PathBuf, u64, String)>> = Mutex::new(None);
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Motivo {
+    /// Editado a mano en la rejilla.
+    Manual,
+    /// Salida del viernes calculada por el modo Automático.
+    Automatico,
+    /// Borrado por `check_fichaje` al no respetar el orden de los tramos.
+    Correccion,
+    /// Semana cerrada con el botón [ ↺ ].
+    Cierre,
+    /// Importado de un fichero externo.
+    Importacion,
+    /// Fichado o editado desde la línea de órdenes.
+    Terminal,
+    /// Fichado desde el menú de la bandeja del sistema.
+    Bandeja,
+    /// Fichado o editado por la API HTTP local.
+    Api,
+    /// Propuesto a partir de los bloqueos y desbloqueos de la sesión.
+    Sesion,
+}
+
+impl Motivo {
+    pub fn nombre(self) -> &'static str {
+        match self {
+            Self::Manual => "Manual",
+            Self::Automatico => "Autom\u{e1}tico",
+            Self::Correccion => "Correcci\u{f3}n",
+            Self::Cierre => "Cierre",
+            Self::Importacion => "Importaci\u{f3}n",
+            Self::Terminal => "Terminal",
+            Self::Bandeja => "Bandeja",
+            Self::Api => "API",
+            Self::Sesion => "Sesi\u{f3}n",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entrada {
+    /// Momento del cambio.
+    pub fecha: DateTime<Local>,
+    /// Día al que pertenece el fichaje.
+    pub dia: NaiveDate,
+    /// Posición en la rejilla (fila * 5 + día).
+    pub celda: usize,
+    pub anterior: String,
+    pub nuevo: String,
+    pub motivo: Motivo,
+    /// SHA-256 (hex) de la entrada anterior más el contenido de esta.
+    #[serde(default)]
+    pub hash: String,
+}
+
+impl Entrada {
+    pub fn nueva(
+        dia: NaiveDate,
+        celda: usize,
+        anterior: &str,
+        nuevo: &str,
+        motivo: Motivo,
+    ) -> Self {
+        Self {
+            fecha: Local::now(),
+            dia,
+            celda,
+            anterior: anterior.to_owned(),
+            nuevo: nuevo.to_owned(),
+            motivo,
+            hash: String::new(),
+        }
+    }
+
+    pub fn fichaje(&self) -> &'static str {
+        crate::FICHAJE_NAMES[self.celda / 5 % 4]
+    }
+
+    /// Hash de la entrada encadenado con `previo`. La fecha se toma en UTC para que el
+    /// resultado no dependa de la zona horaria del equipo que verifica.
+    pub fn calcular_hash(&self, previo: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(previo.as_bytes());
+        hasher.update(
+            format!(
+                "|{}|{}|{}|{}|{}|{:?}",
+                self.fecha.with_timezone(&Utc).to_rfc3339(),
+                self.dia,
+                self.celda,
+                self.anterior,
+                self.nuevo,
+                self.motivo
+            )
+            .as_bytes(),
+        );
+        hex(&hasher.finalize())
+    }
+}
+
+/// Resultado de recorrer la cadena de hashes.
+pub enum Verificacion {
+    /// Número de entradas encadenadas.
+    Integra(usize),
+    /// Índice (desde 0) de la primera entrada cuyo hash no encaja.
+    Rota(usize, Entrada),
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub fn ruta() -> Option<PathBuf> {
+    crate::data_dir().map(|dir| dir.join(FICHERO))
+}
+
+/// Entradas para las celdas que han cambiado respecto a `previos`. Las celdas en edición se
+/// ignoran hasta que se confirman.
+pub fn cambios(
+    previos: &[String],
+    fichajes: &[Cell],
+    lunes: NaiveDate,
+    motivo: Motivo,
+) -> Vec<Entrada> {
+    fichajes
+        .iter()
+        .zip(previos)
+        .enumerate()
+        .filter(|(_, (cell, previo))| !cell.is_edit && cell.cell.trim() != previo.as_str())
+        .map(|(celda, (cell, previo))| {
+            let dia = lunes + Duration::days((celda % 5) as i64);
+            Entrada::nueva(dia, celda, previo, cell.cell.trim(), motivo)
+        })
+        .collect()
+}
+
+pub fn registrar(entradas: &[Entrada]) -> io::Result<()> {
+    if entradas.is_empty() {
+        return Ok(());
+    }
+
+    let ruta = ruta().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "data_dir"))?;
+    if let Some(dir) = ruta.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    registrar_en(&ruta, entradas)
+}
+
+fn registrar_en(ruta: &Path, entradas: &[Entrada]) -> io::Result<()> {
+    let mut ultimo = ULTIMO
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(ruta)?;
+    let largo = file.metadata()?.len();
+    let mut previo = match ultimo.take() {
+        Some((fichero, visto, hash)) if fichero == ruta && visto == largo => hash,
+        _ => ultimo_hash(&mut file, largo)?,
+    };
+
+    for entrada in entradas {
+        let mut entrada = entrada.clone();
+        entrada.hash = entrada.calcular_hash(&previo);
+        writeln!(file, "{}", serde_json::to_string(&entrada)?)?;
+        previo = entrada.hash;
+    }
+    file.sync_all()?;
+
+    // Si algo falla antes de aquí, la próxima vez se vuelve a leer del fichero.
+    *ultimo = Some((ruta.to_owned(), file.metadata()?.len(), previo));
+    Ok(())
+}
+
+/// Hash de la última entrada del registro, leyendo sólo el final del fichero: una entrada ocupa
+/// unos 250 bytes, así que en los últimos 4 KiB hay alguna entera.
+fn ultimo_hash(file: &mut File, largo: u64) -> io::Result<String> {
+    let mut cola = Vec::new();
+    file.seek(SeekFrom::Start(largo.saturating_sub(4096)))?;
+    file.read_to_end(&mut cola)?;
+
+    Ok(String::from_utf8_lossy(&cola)
+        .lines()
+        .rev()
+        .find_map(|linea| serde_json::from_str::<Entrada>(linea).ok())
+        .map(|entrada| entrada.hash)
+        .unwrap_or_default())
+}
+
+/// Recorre el registro desde la primera entrada con hash y devuelve el primer enlace roto, si lo
+/// hay. Las entradas anteriores se escribieron antes de encadenar el registro.
+pub fn verificar(entradas: &[Entrada]) -> Verificacion {
+    let inicio = entradas
+        .iter()
+        .position(|e| !e.hash.is_empty())
+        .unwrap_or(entradas.len());
+    let mut previo = String::new();
+
+    for (n, entrada) in entradas.iter().enumerate().skip(inicio) {
+        if entrada.calcular_hash(&previo) != entrada.hash {
+            return Verificacion::Rota(n, entrada.clone());
+        }
+        previo = entrada.hash.clone();
+    }
+
+    Verificacion::Integra(entradas.len() - inicio)
+}
+
+pub fn leer() -> Vec<Entrada> {
+    ruta().map(|ruta| leer_de(&ruta)).unwrap_or_default()
+}
+
+fn leer_de(ruta: &Path) -> Vec<Entrada> {
+    let Some(file) = File::open(ruta).ok() else {
+        return Vec::new();
+    };
+
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|linea| serde_json::from_str(&linea).ok())
+        .collect()
+}
+
+/// Copia el registro en CSV dentro de `dir` y devuelve la ruta del fichero.
+pub fn exportar(dir: &std::path::Path) -> io::Result<PathBuf> {
+    let ruta = dir.join(format!(
+        "horario-auditoria-{}.csv",
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+
+    let mut file = File::create(&ruta)?;
+    writeln!(file, "fecha;dia;fichaje;anterior;nuevo;motivo;hash")?;
+    for entrada in leer() {
+        writeln!(
+            file,
+            "{};{};{};{};{};{};{}",
+            entrada.fecha.to_rfc3339(),
+            entrada.dia,
+            entrada.fichaje(),
+            entrada.anterior,
+            entrada.nuevo,
+            entrada.motivo.nombre(),
+            entrada.hash
+        )?;
+    }
+
+    Ok(ruta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entradas(n: usize) -> Vec<Entrada> {
+        let dia = NaiveDate::from_ymd_opt(2026, 10, 12).unwrap();
+        (0..n)
+            .map(|celda| Entrada::nueva(dia, celda, "", "0800", Motivo::Manual))
+            .collect()
+    }
+
+    fn encadenar(entradas: &mut [Entrada], mut previo: String) {
+        for entrada in entradas {
+            entrada.hash = entrada.calcular_hash(&previo);
+            previo = entrada.hash.clone();
+        }
+    }
+
+    fn temporal(nombre: &str) -> PathBuf {
+        let ruta = std::env::temp_dir().join(format!("horario-{}-{nombre}", std::process::id()));
+        let _ = std::fs::remove_file(&ruta);
+        ruta
+    }
+
+    #[test]
+    fn verificar_detecta_cambios_y_borrados() {
+        let mut registro = entradas(4);
+        encadenar(&mut registro, String::new());
+        assert!(matches!(verificar(&registro), Verificacion::Integra(4)));
+
+        let mut cambiado = registro.clone();
+        cambiado[2].nuevo = "0900".to_owned();
+        assert!(matches!(verificar(&cambiado), Verificacion::Rota(2, _)));
+
+        let mut borrado = registro.clone();
+        borrado.remove(1);
+        assert!(matches!(verificar(&borrado), Verificacion::Rota(1, _)));
+
+        // Rehacer el hash de la cambiada no basta: rompe el enlace siguiente.
+        let previo = cambiado[1].hash.clone();
+        cambiado[2].hash = cambiado[2].calcular_hash(&previo);
+        assert!(matches!(verificar(&cambiado), Verificacion::Rota(3, _)));
+    }
+
+    #[test]
+    fn verificar_empieza_en_la_primera_entrada_encadenada() {
+        let mut registro = entradas(5);
+        encadenar(&mut registro[2..], String::new());
+        assert!(matches!(verificar(&registro), Verificacion::Integra(3)));
+        assert!(matches!(verificar(&[]), Verificacion::Integra(0)));
+    }
+
+    #[test]
+    fn registrar_sigue_la_cadena_de_otro_proceso() {
+        let ruta = temporal("auditoria.jsonl");
+        registrar_en(&ruta, &entradas(2)).unwrap();
+
+        // Otro proceso añade una entrada detrás.
+        let mut ajena = entradas(1);
+        let previo = leer_de(&ruta).last().unwrap().hash.clone();
+        encadenar(&mut ajena, previo);
+        let mut file = OpenOptions::new().append(true).open(&ruta).unwrap();
+        writeln!(file, "{}", serde_json::to_string(&ajena[0]).unwrap()).unwrap();
+
+        registrar_en(&ruta, &entradas(2)).unwrap();
+        let registro = leer_de(&ruta);
+        let _ = std::fs::remove_file(&ruta);
+        assert_eq!(registro.len(), 5);
+        assert!(matches!(verificar(&registro), Verificacion::Integra(5)));
+    }
+}
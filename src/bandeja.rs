@@ -0,0 +1,178 @@
+use std::fmt;
+use std::sync::mpsc::Receiver;
+
+use eframe::egui::Context;
+
+use crate::estado::Estado;
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Bandeja del Sistema                                                                 +++
+// +----------------------------------------------------------------------------------------------+
+
+// Icono StatusNotifierItem (sólo Linux) con el estado en el tooltip y un menú para fichar, abrir
+// la ventana y salir. El menú corre en su propio hilo y manda las órdenes a la ventana, que es la
+// dueña de los datos.
+
+/// La ventana va por Wayland, donde una aplicación no puede esconder su ventana ni volver a
+/// mostrarla si está minimizada.
+pub fn wayland() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some()
+        && std::env::var("WINIT_UNIX_BACKEND").map_or(true, |backend| backend != "x11")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orden {
+    Fichar,
+    Mostrar,
+    Salir,
+}
+
+pub struct Bandeja {
+    ordenes: Receiver<Orden>,
+    #[cfg(target_os = "linux")]
+    icono: ksni::blocking::Handle<sni::Icono>,
+    /// Error de la última orden, se muestra en el tooltip.
+    pub aviso: String,
+    /// Último texto enviado al icono, para no repetir la actualización en cada fotograma.
+    mostrado: String,
+}
+
+impl fmt::Debug for Bandeja {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bandeja")
+            .field("mostrado", &self.mostrado)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Bandeja {
+    /// Crea el icono, o `None` si el escritorio no tiene bandeja.
+    pub fn iniciar(ctx: &Context) -> Option<Self> {
+        #[cfg(target_os = "linux")]
+        {
+            use ksni::blocking::TrayMethods;
+
+            let (enviar, ordenes) = std::sync::mpsc::channel();
+            let icono = sni::Icono {
+                estado: Estado::default(),
+                aviso: String::new(),
+                ordenes: enviar,
+                ctx: ctx.clone(),
+            };
+
+            icono.spawn().ok().map(|icono| Self {
+                ordenes,
+                icono,
+                aviso: String::new(),
+                mostrado: String::new(),
+            })
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = ctx;
+            None
+        }
+    }
+
+    /// Órdenes del menú pendientes de atender.
+    pub fn ordenes(&self) -> Vec<Orden> {
+        self.ordenes.try_iter().collect()
+    }
+
+    /// Cambia el icono y el tooltip si ha cambiado el estado o el aviso.
+    pub fn actualizar(&mut self, estado: &Estado) {
+        let mostrado = format!("{}{}", crate::barra::detalle(estado), self.aviso);
+        if mostrado == self.mostrado {
+            return;
+        }
+        self.mostrado = mostrado;
+
+        #[cfg(target_os = "linux")]
+        self.icono.update(|icono| {
+            icono.estado = estado.clone();
+            icono.aviso = self.aviso.clone();
+        });
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sni {
+    use std::sync::mpsc::Sender;
+
+    use eframe::egui::Context;
+    use ksni::menu::StandardItem;
+    use ksni::{MenuItem, ToolTip, Tray};
+
+    use super::Orden;
+    use crate::estado::Estado;
+
+    pub struct Icono {
+        pub estado: Estado,
+        pub aviso: String,
+        pub ordenes: Sender<Orden>,
+        pub ctx: Context,
+    }
+
+    impl Icono {
+        fn enviar(&self, orden: Orden) {
+            let _ = self.ordenes.send(orden);
+            self.ctx.request_repaint();
+        }
+
+        fn item(etiqueta: &str, icono: &str, orden: Orden) -> MenuItem<Self> {
+            StandardItem {
+                label: etiqueta.to_owned(),
+                icon_name: icono.to_owned(),
+                activate: Box::new(move |icono: &mut Self| icono.enviar(orden)),
+                ..Default::default()
+            }
+            .into()
+        }
+    }
+
+    impl Tray for Icono {
+        fn id(&self) -> String {
+            "horario".to_owned()
+        }
+
+        fn title(&self) -> String {
+            crate::APPNAME.to_owned()
+        }
+
+        fn icon_name(&self) -> String {
+            if self.estado.dentro {
+                "media-playback-start"
+            } else {
+                "media-playback-pause"
+            }
+            .to_owned()
+        }
+
+        fn tool_tip(&self) -> ToolTip {
+            ToolTip {
+                title: crate::APPNAME.to_owned(),
+                description: format!("{}\n{}", crate::barra::detalle(&self.estado), self.aviso)
+                    .trim_end()
+                    .to_owned(),
+                ..Default::default()
+            }
+        }
+
+        fn activate(&mut self, _x: i32, _y: i32) {
+            if !super::wayland() {
+                self.enviar(Orden::Mostrar);
+            }
+        }
+
+        fn menu(&self) -> Vec<MenuItem<Self>> {
+            let mut menu = vec![Self::item("Fichar", "appointment-new", Orden::Fichar)];
+            if !super::wayland() {
+                menu.push(Self::item("Abrir horario", "window-new", Orden::Mostrar));
+            }
+            menu.push(MenuItem::Separator);
+            menu.push(Self::item("Salir", "application-exit", Orden::Salir));
+            menu
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use serde_json::json;
+
+use crate::calculo;
+use crate::estado::{self, Estado};
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Barra de Estado                                                                     +++
+// +----------------------------------------------------------------------------------------------+
+
+// Una línea de estado para waybar, i3blocks o polybar: fichado o no, trabajado hoy y saldo de la
+// semana, con el color del saldo de la ventana.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Formato {
+    /// Objeto JSON con `text`, `tooltip`, `class` y `alt`.
+    Waybar,
+    /// `full_text`, `short_text` y `color`, una línea cada uno.
+    I3blocks,
+    /// Una sola línea, para polybar.
+    Texto,
+}
+
+const FICHADO: [&str; 2] = ["\u{23f8}", "\u{23f5}"];
+
+const ERROR: &str = "\u{26a0}";
+
+fn color(saldo: i64) -> String {
+    let color = crate::color_saldo(saldo).0;
+    format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b())
+}
+
+fn texto(estado: &Estado) -> String {
+    format!(
+        "{} {}  {}",
+        FICHADO[usize::from(estado.dentro)],
+        calculo::hhmm(estado.hoy),
+        estado::saldo_texto(estado.saldo)
+    )
+}
+
+pub fn detalle(estado: &Estado) -> String {
+    let mut detalle = format!(
+        "{}\nHoy: {}\nSaldo semana: {}",
+        if estado.dentro {
+            "Fichado"
+        } else {
+            "Sin fichar"
+        },
+        calculo::horas(estado.hoy),
+        estado::saldo_texto(estado.saldo)
+    );
+    if let Some(salida) = estado.salida {
+        detalle.push_str(&format!("\nSalida prevista: {}", salida.format("%H:%M")));
+    }
+    detalle
+}
+
+pub fn generar(estado: &Estado, formato: Formato) -> String {
+    match formato {
+        Formato::Waybar => json!({
+            "text": texto(estado),
+            "tooltip": detalle(estado),
+            "alt": if estado.dentro { "dentro" } else { "fuera" },
+            "class": [
+                if estado.dentro { "dentro" } else { "fuera" },
+                match estado.saldo {
+                    0 => "cero",
+                    s if s < 0 => "negativo",
+                    _ => "positivo",
+                },
+            ],
+        })
+        .to_string(),
+        Formato::I3blocks => format!(
+            "{}\n{}\n{}",
+            texto(estado),
+            estado::saldo_texto(estado.saldo),
+            color(estado.saldo)
+        ),
+        Formato::Texto => texto(estado),
+    }
+}
+
+/// Lo que se muestra si no se ha podido leer el estado, para que la barra no se quede con el
+/// último.
+pub fn error(error: &str, formato: Formato) -> String {
+    let linea = error.lines().next().unwrap_or_default();
+    match formato {
+        Formato::Waybar => json!({
+            "text": ERROR,
+            "tooltip": error,
+            "alt": "error",
+            "class": ["error"],
+        })
+        .to_string(),
+        Formato::I3blocks => format!("{ERROR} {linea}\n{ERROR}\n#ff0000"),
+        Formato::Texto => format!("{ERROR} {linea}"),
+    }
+}
@@ -0,0 +1,260 @@
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::historial;
+use crate::Datos;
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Bolsa de Horas                                                                      +++
+// +----------------------------------------------------------------------------------------------+
+
+// Saldo acumulado de todas las semanas con el tope y la caducidad del convenio. Lo que sobra cada
+// semana se guarda en el trimestre en que se hizo y se gasta primero lo más antiguo. Lo que pasa
+// del tope se pierde y lo que sigue sin gastar al acabar el plazo caduca. Si se debe tiempo, lo
+// que sobra paga primero la deuda.
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Config {
+    /// Minutos de saldo a favor como mucho; 0 = sin tope.
+    pub tope_positivo: u32,
+    /// Minutos de deuda como mucho; 0 = sin tope.
+    pub tope_negativo: u32,
+    /// Trimestres siguientes al de cada sobrante para gastarlo; 0 = no caduca.
+    pub trimestres: u32,
+}
+
+/// Sobrante de un trimestre que aún no se ha gastado.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lote {
+    /// Primer día del trimestre.
+    pub trimestre: NaiveDate,
+    /// Último día para gastarlo.
+    pub caduca: Option<NaiveDate>,
+    pub segundos: i64,
+}
+
+impl Lote {
+    /// `"T3 2026"`
+    pub fn nombre(&self) -> String {
+        format!(
+            "T{} {}",
+            self.trimestre.month0() / 3 + 1,
+            self.trimestre.year()
+        )
+    }
+}
+
+/// Estado de la bolsa, en segundos.
+#[derive(Clone, Debug, Default)]
+pub struct Bolsa {
+    /// Del más antiguo al más reciente.
+    pub lotes: Vec<Lote>,
+    pub deuda: i64,
+    /// Sobrante que pasó del tope positivo.
+    pub perdidas: i64,
+    /// Sobrante que no se gastó a tiempo.
+    pub caducadas: i64,
+    /// Deuda que pasó del tope negativo: no se arrastra.
+    pub descubierto: i64,
+}
+
+impl Bolsa {
+    pub fn saldo(&self) -> i64 {
+        self.lotes.iter().map(|l| l.segundos).sum::<i64>() - self.deuda
+    }
+
+    /// El próximo sobrante que caduca.
+    pub fn en_riesgo(&self) -> Option<&Lote> {
+        self.lotes.iter().find(|l| l.caduca.is_some())
+    }
+
+    fn caducar(&mut self, antes_de: NaiveDate) {
+        let (caducados, lotes): (Vec<Lote>, Vec<Lote>) = self
+            .lotes
+            .drain(..)
+            .partition(|l| l.caduca.is_some_and(|c| c < antes_de));
+        self.lotes = lotes;
+        self.caducadas += caducados.iter().map(|l| l.segundos).sum::<i64>();
+    }
+
+    fn sumar(&mut self, saldo: i64, trimestre: NaiveDate, config: &Config) {
+        if saldo >= 0 {
+            let pagado = saldo.min(self.deuda);
+            self.deuda -= pagado;
+            let sobra = saldo - pagado;
+            if sobra > 0 {
+                match self.lotes.last_mut() {
+                    Some(lote) if lote.trimestre == trimestre => lote.segundos += sobra,
+                    _ => self.lotes.push(Lote {
+                        trimestre,
+                        caduca: caducidad(trimestre, config.trimestres),
+                        segundos: sobra,
+                    }),
+                }
+            }
+        } else {
+            // Se gasta lo más antiguo; lo que falta es deuda.
+            let mut falta = -saldo;
+            for lote in &mut self.lotes {
+                let gastado = falta.min(lote.segundos);
+                lote.segundos -= gastado;
+                falta -= gastado;
+            }
+            self.lotes.retain(|l| l.segundos > 0);
+            self.deuda += falta;
+        }
+
+        // Topes: lo que sobra sale de lo más reciente.
+        let tope = i64::from(config.tope_positivo) * 60;
+        let mut exceso = (self.saldo() - tope).max(0);
+        if tope > 0 && exceso > 0 {
+            self.perdidas += exceso;
+            for lote in self.lotes.iter_mut().rev() {
+                let quitado = exceso.min(lote.segundos);
+                lote.segundos -= quitado;
+                exceso -= quitado;
+            }
+            self.lotes.retain(|l| l.segundos > 0);
+        }
+        let tope = i64::from(config.tope_negativo) * 60;
+        if tope > 0 && self.deuda > tope {
+            self.descubierto += self.deuda - tope;
+            self.deuda = tope;
+        }
+    }
+}
+
+/// Primer día del trimestre de `fecha`.
+fn trimestre(fecha: NaiveDate) -> NaiveDate {
+    NaiveDate::from_ymd_opt(fecha.year(), fecha.month0() / 3 * 3 + 1, 1).unwrap_or(fecha)
+}
+
+/// Último día del trimestre que va `trimestres` después del de `inicio`.
+fn caducidad(inicio: NaiveDate, trimestres: u32) -> Option<NaiveDate> {
+    if trimestres == 0 {
+        return None;
+    }
+    let meses = inicio.year() * 12 + inicio.month0() as i32 + (trimestres as i32 + 1) * 3;
+    NaiveDate::from_ymd_opt(meses / 12, meses as u32 % 12 + 1, 1).map(|d| d - Duration::days(1))
+}
+
+/// La bolsa con todas las semanas, la actual incluida, y lo caducado hasta `hoy`.
+pub fn calcular(datos: &Datos, hoy: NaiveDate) -> Bolsa {
+    let config = &datos.bolsa;
+    let mut bolsa = Bolsa::default();
+
+    for semana in historial::semanas(datos) {
+        if semana.lunes > hoy {
+            break;
+        }
+        // Sólo hasta hoy: lo que haya después en la semana en curso no se ha trabajado todavía.
+        // Sin el Tiempo a Recuperar: es deuda que ya está en la bolsa, y contarlo otra vez en
+        // cada semana en que siga puesto la duplicaría.
+        let resumen = semana.resumen();
+        let saldo = (0..5)
+            .filter(|&dia| semana.fecha(dia) <= hoy)
+            .map(|dia| resumen.dias[dia] - resumen.objetivos[dia])
+            .sum::<i64>()
+            - semana.extras_entre(semana.lunes, hoy);
+        bolsa.caducar(semana.lunes);
+        bolsa.sumar(saldo, trimestre(semana.lunes), config);
+    }
+    bolsa.caducar(hoy);
+
+    bolsa
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const H: i64 = 3600;
+
+    fn fecha(mes: u32, dia: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, mes, dia).unwrap()
+    }
+
+    #[test]
+    fn caduca_al_final_del_trimestre() {
+        assert_eq!(trimestre(fecha(11, 15)), fecha(10, 1));
+        assert_eq!(caducidad(fecha(1, 1), 1), Some(fecha(6, 30)));
+        assert_eq!(caducidad(fecha(7, 1), 1), Some(fecha(12, 31)));
+        assert_eq!(
+            caducidad(fecha(10, 1), 1),
+            NaiveDate::from_ymd_opt(2027, 3, 31)
+        );
+        assert_eq!(caducidad(fecha(4, 1), 2), Some(fecha(12, 31)));
+        assert_eq!(caducidad(fecha(4, 1), 0), None);
+    }
+
+    #[test]
+    fn gasta_lo_mas_antiguo_y_paga_la_deuda() {
+        let config = Config {
+            trimestres: 1,
+            ..Config::default()
+        };
+        let mut bolsa = Bolsa::default();
+        bolsa.sumar(3 * H, fecha(1, 1), &config);
+        bolsa.sumar(2 * H, fecha(4, 1), &config);
+        bolsa.sumar(-4 * H, fecha(4, 1), &config);
+        assert_eq!(
+            bolsa.lotes,
+            vec![Lote {
+                trimestre: fecha(4, 1),
+                caduca: Some(fecha(9, 30)),
+                segundos: H,
+            }]
+        );
+
+        bolsa.sumar(-3 * H, fecha(4, 1), &config);
+        assert!(bolsa.lotes.is_empty());
+        assert_eq!(bolsa.deuda, 2 * H);
+        bolsa.sumar(3 * H, fecha(7, 1), &config);
+        assert_eq!(bolsa.deuda, 0);
+        assert_eq!(bolsa.saldo(), H);
+
+        bolsa.caducar(fecha(12, 31));
+        assert_eq!(bolsa.saldo(), H);
+        bolsa.caducar(fecha(12, 31) + Duration::days(1));
+        assert_eq!(bolsa.saldo(), 0);
+        assert_eq!(bolsa.caducadas, H);
+    }
+
+    #[test]
+    fn topes() {
+        let config = Config {
+            tope_positivo: 600,
+            tope_negativo: 60,
+            trimestres: 0,
+        };
+        let mut bolsa = Bolsa::default();
+        bolsa.sumar(8 * H, fecha(1, 1), &config);
+        bolsa.sumar(4 * H, fecha(4, 1), &config);
+        assert_eq!(bolsa.saldo(), 10 * H);
+        assert_eq!(bolsa.perdidas, 2 * H);
+        assert_eq!(bolsa.lotes[1].segundos, 2 * H);
+
+        bolsa.sumar(-13 * H, fecha(4, 1), &config);
+        assert_eq!(bolsa.deuda, H);
+        assert_eq!(bolsa.descubierto, 2 * H);
+    }
+
+    #[test]
+    fn la_semana_en_curso_cuenta_hasta_hoy() {
+        let mut datos = Datos {
+            lunes: Some(fecha(3, 9)),
+            ..Datos::default()
+        };
+        datos.reglas.extras.diaria = 8 * 60;
+        // El lunes una hora de más, media extraordinaria, y el jueves ya importado.
+        for (dia, salida) in [(0, "1600"), (1, "1500"), (3, "1000")] {
+            datos.fichajes[dia].cell = "      0730".to_owned();
+            datos.fichajes[dia + 5].cell = format!("      {salida}");
+        }
+
+        // Miércoles sin fichar: cuenta la jornada, como en el saldo.
+        assert_eq!(calcular(&datos, fecha(3, 11)).saldo(), 30 * 60);
+        assert_eq!(calcular(&datos, fecha(3, 13)).saldo(), 30 * 60 - 5 * H);
+    }
+}
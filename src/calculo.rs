@@ -0,0 +1,276 @@
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+
+use crate::reglas::{Extra, Reglas};
+use crate::{Cell, HM};
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Calculo                                                                             +++
+// +----------------------------------------------------------------------------------------------+
+
+/// Día que no se trabaja. No cuenta en el objetivo de la semana.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Ausencia {
+    Festivo,
+    Vacaciones,
+    Baja,
+    Permiso,
+}
+
+impl Ausencia {
+    pub const TODAS: [Ausencia; 4] = [
+        Ausencia::Festivo,
+        Ausencia::Vacaciones,
+        Ausencia::Baja,
+        Ausencia::Permiso,
+    ];
+
+    pub fn nombre(self) -> &'static str {
+        match self {
+            Self::Festivo => "Festivo",
+            Self::Vacaciones => "Vacaciones",
+            Self::Baja => "Baja",
+            Self::Permiso => "Permiso",
+        }
+    }
+}
+
+/// Totales de una semana de fichajes, en segundos.
+#[derive(Clone, Debug, Default)]
+pub struct Resumen {
+    /// Tiempo de cada día (Lunes..Viernes). Un día sin fichajes ni ausencia cuenta como jornada
+    /// completa.
+    pub dias: [i64; 5],
+    /// Jornada de cada día: 0 si hay ausencia.
+    pub objetivos: [i64; 5],
+    /// Descontado de cada día por no hacer el descanso obligatorio.
+    pub descansos: [i64; 5],
+    /// Tiempo de la franja obligatoria de cada día sin cubrir por los tramos fichados.
+    pub franjas: [i64; 5],
+    /// Horas extraordinarias de cada tipo, en el orden de `Extra::TODAS`. No cuentan en el saldo.
+    pub extras: [i64; 5],
+    /// Tiempo del sábado y el domingo: está en `total` y en las extraordinarias, no en `dias`.
+    pub fin_de_semana: i64,
+    /// Tiempo fichado el viernes, sin completar.
+    pub viernes: i64,
+    /// Tiempo fichado por las tardes.
+    pub tardes: i64,
+    pub total: i64,
+    /// Jornada de los días sin ausencia más el Tiempo a Recuperar.
+    pub objetivo: i64,
+    pub objetivo_tardes: i64,
+    pub recuperar: i64,
+}
+
+impl Resumen {
+    pub fn saldo(&self) -> i64 {
+        self.total - self.extra() - self.objetivo
+    }
+
+    pub fn extra(&self) -> i64 {
+        self.extras.iter().sum()
+    }
+}
+
+/// Segundos desde medianoche de una celda `HHMM`; 0 si está vacía o no es válida.
+pub fn segundos(cell: &str) -> i64 {
+    let zero = NaiveTime::from_hms_opt(0, 0, 0).unwrap_or_default();
+    NaiveTime::parse_from_str(cell.trim(), HM)
+        .unwrap_or(zero)
+        .signed_duration_since(zero)
+        .num_seconds()
+}
+
+/// Duración de un tramo entrada/salida; 0 si falta la entrada o la salida es anterior.
+pub fn tramo(entrada: &str, salida: &str) -> i64 {
+    let start_time = segundos(entrada);
+    let end_time = segundos(salida);
+    match end_time - start_time {
+        x if x > 0 && start_time != 0 => x,
+        _ => 0,
+    }
+}
+
+/// Lo que cuenta de un tramo del día `dia`: recortadas al horario computable y redondeadas las
+/// horas, y después la duración.
+pub fn computado(entrada: &str, salida: &str, dia: usize, reglas: &Reglas) -> i64 {
+    if tramo(entrada, salida) == 0 {
+        return 0;
+    }
+
+    let duracion = reglas.salida(dia, segundos(salida)) - reglas.entrada(dia, segundos(entrada));
+    reglas.tramos.aplicar(duracion.max(0))
+}
+
+pub fn dia_vacio(fichajes: &[Cell], dia: usize) -> bool {
+    (0..4).all(|n| fichajes[dia + n * 5].cell.trim().is_empty())
+}
+
+/// Los cuatro fichajes del día van en orden, sin contar los vacíos entre vecinos.
+pub fn orden_valido(fichajes: &[Cell], dia: usize) -> bool {
+    let t = |fila: usize| segundos(&fichajes[dia + fila * 5].cell);
+    let (t1, t2, t3, t4) = (t(0), t(1), t(2), t(3));
+
+    !(t1 > t2 && t2 != 0 || t3 > t4 && t4 != 0 || t2 > t3 && t3 != 0)
+}
+
+/// Tramos completos del día, `(entrada, salida)` en segundos tal y como se ficharon.
+pub fn tramos(fichajes: &[Cell], dia: usize) -> Vec<(i64, i64)> {
+    [0, 10]
+        .iter()
+        .map(|fila| (&fichajes[dia + fila].cell, &fichajes[dia + fila + 5].cell))
+        .filter(|(entrada, salida)| tramo(entrada, salida) > 0)
+        .map(|(entrada, salida)| (segundos(entrada), segundos(salida)))
+        .collect()
+}
+
+/// Tramos completos del día con las horas que cuentan.
+fn tramos_computados(fichajes: &[Cell], dia: usize, reglas: &Reglas) -> Vec<(i64, i64)> {
+    tramos(fichajes, dia)
+        .into_iter()
+        .map(|(entrada, salida)| (reglas.entrada(dia, entrada), reglas.salida(dia, salida)))
+        .collect()
+}
+
+/// Descontado de un día por no parar lo que piden las reglas.
+pub fn descanso(fichajes: &[Cell], dia: usize, reglas: &Reglas) -> i64 {
+    reglas
+        .descanso
+        .descuento(&tramos_computados(fichajes, dia, reglas))
+}
+
+/// Tiempo de la franja obligatoria sin cubrir. Un día vacío o con un tramo todavía abierto no
+/// se marca: no ha empezado o no ha terminado.
+pub fn fuera_de_franja(fichajes: &[Cell], dia: usize, reglas: &Reglas) -> i64 {
+    let abierto = [0, 10].iter().any(|fila| {
+        !fichajes[dia + fila].cell.trim().is_empty()
+            && fichajes[dia + fila + 5].cell.trim().is_empty()
+    });
+    if dia_vacio(fichajes, dia) || abierto {
+        return 0;
+    }
+
+    reglas.franjas[dia].sin_cubrir(&tramos(fichajes, dia))
+}
+
+/// Tiempo computado en un día (0 = Lunes), sin completar los días vacíos.
+pub fn fichado(fichajes: &[Cell], dia: usize, reglas: &Reglas) -> i64 {
+    let trabajado = computado(&fichajes[dia].cell, &fichajes[dia + 5].cell, dia, reglas)
+        + computado(
+            &fichajes[dia + 10].cell,
+            &fichajes[dia + 15].cell,
+            dia,
+            reglas,
+        );
+    reglas
+        .dias
+        .aplicar((trabajado - descanso(fichajes, dia, reglas)).max(0))
+}
+
+/// Horas extraordinarias de la semana, de cada tipo.
+pub fn extras(fichajes: &[Cell], ausencias: &[Option<Ausencia>; 5], reglas: &Reglas) -> [i64; 5] {
+    let config = &reglas.extras;
+    let mut extras = [0; 5];
+    let mut ordinario = 0;
+
+    for (dia, ausencia) in ausencias.iter().enumerate() {
+        let trabajado = fichado(fichajes, dia, reglas);
+        if config.festivos && *ausencia == Some(Ausencia::Festivo) {
+            extras[Extra::Festivo as usize] += trabajado;
+            continue;
+        }
+
+        let diaria = match config.diaria {
+            0 => 0,
+            minutos => (trabajado - i64::from(minutos) * 60).max(0),
+        };
+        let nocturna = config
+            .nocturno(&tramos_computados(fichajes, dia, reglas))
+            .min(trabajado - diaria);
+
+        extras[Extra::Diaria as usize] += diaria;
+        extras[Extra::Nocturna as usize] += nocturna;
+        ordinario += trabajado - diaria - nocturna;
+    }
+
+    extras[Extra::Semanal as usize] = match config.semanal {
+        0 => 0,
+        minutos => (ordinario - i64::from(minutos) * 60).max(0),
+    };
+    extras
+}
+
+pub fn resumen(
+    fichajes: &[Cell],
+    config: &[Cell],
+    ausencias: &[Option<Ausencia>; 5],
+    fin_de_semana: i64,
+    reglas: &Reglas,
+) -> Resumen {
+    let jornada = segundos(&config[0].cell);
+    let recuperar = segundos(&config[2].cell);
+
+    let mut dias = [0; 5];
+    let mut objetivos = [jornada; 5];
+    for dia in 0..5 {
+        dias[dia] = if ausencias[dia].is_some() {
+            objetivos[dia] = 0;
+            fichado(fichajes, dia, reglas)
+        } else if dia_vacio(fichajes, dia) {
+            jornada
+        } else {
+            fichado(fichajes, dia, reglas)
+        };
+    }
+
+    let mut extras = extras(fichajes, ausencias, reglas);
+    extras[Extra::FinDeSemana as usize] = fin_de_semana;
+
+    Resumen {
+        dias,
+        objetivos,
+        descansos: std::array::from_fn(|dia| descanso(fichajes, dia, reglas)),
+        extras,
+        fin_de_semana,
+        franjas: std::array::from_fn(|dia| match ausencias[dia] {
+            Some(_) => 0,
+            None => fuera_de_franja(fichajes, dia, reglas),
+        }),
+        viernes: fichado(fichajes, 4, reglas),
+        tardes: (0..5)
+            .map(|dia| {
+                computado(
+                    &fichajes[dia + 10].cell,
+                    &fichajes[dia + 15].cell,
+                    dia,
+                    reglas,
+                )
+            })
+            .sum(),
+        total: dias.iter().sum::<i64>() + fin_de_semana,
+        objetivo: objetivos.iter().sum::<i64>() + recuperar,
+        objetivo_tardes: segundos(&config[1].cell),
+        recuperar,
+    }
+}
+
+/// Hora `HH:MM` de una celda de la rejilla, o vacío.
+pub fn hora(cell: &str) -> String {
+    match segundos(cell) {
+        0 => String::new(),
+        s => format!("{:02}:{:02}", s / 3600, s % 3600 / 60),
+    }
+}
+
+/// `"Xh  Ym"` de una cantidad de segundos, sin signo.
+pub fn horas(segundos: i64) -> String {
+    let time = secfmt::from(segundos.unsigned_abs());
+    format!("{}h  {}m", time.days * 24 + time.hours, time.minutes)
+}
+
+/// `"H:MM"` con signo si es negativo, para hojas de cálculo.
+pub fn hhmm(segundos: i64) -> String {
+    let signo = if segundos < 0 { "-" } else { "" };
+    let segundos = segundos.abs();
+    format!("{}{}:{:02}", signo, segundos / 3600, segundos % 3600 / 60)
+}
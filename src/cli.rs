@@ -0,0 +1,361 @@
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::time::Duration;
+
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+
+use crate::almacen::Almacen;
+use crate::auditoria::{self, Motivo};
+use crate::barra::{self, Formato};
+use crate::calculo;
+use crate::estado;
+use crate::firma;
+use crate::historial::{self, Semana};
+use crate::instancia;
+use crate::webhook;
+use crate::{copia, csv, ics, pdf};
+use crate::{Datos, DAYS_WEEK_NAMES, DMY, FICHAJE_NAMES, HM};
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Línea de Órdenes                                                                    +++
+// +----------------------------------------------------------------------------------------------+
+
+// Fichar y consultar sin abrir la ventana, sobre el mismo `app.ron`. Los cambios se auditan con
+// el motivo Terminal igual que los de la rejilla. Si la ventana está abierta, las órdenes se le
+// mandan por el socket de `instancia` y las ejecuta ella sobre sus datos.
+
+const AYUDA: &str = "\
+Uso: horario [ORDEN]
+
+Sin orden abre la ventana.
+
+  punch [HHMM]              Ficha ahora (o a HHMM) en la primera casilla libre de hoy
+  status                    Trabajado hoy, saldo de la semana y hora de salida prevista
+  set DIA FICHAJE HHMM|-    Cambia una casilla: DIA mon..fri, lun..vie o 1..5;
+                            FICHAJE 1..4 (Entrada/Salida Ma\u{f1}ana, Entrada/Salida Tarde);
+                            - la deja vac\u{ed}a
+  export --csv|--ics|--json|--pdf [--desde DD/MM/AAAA] [--hasta DD/MM/AAAA] [--ausencias]
+                            CSV, ICS y JSON por la salida est\u{e1}ndar; el PDF (mes de --desde)
+                            se guarda en Descargas. Por defecto, el mes en curso
+  bar [--waybar|--i3blocks|--texto] [--follow]
+                            Estado para la barra (waybar por defecto); --follow lo repite
+                            cada minuto
+  close                     Cierra la semana: la guarda en el historial y vac\u{ed}a la rejilla
+  show                      Muestra la ventana abierta
+  api                       Sirve la API HTTP local sin abrir la ventana
+  help                      Esta ayuda
+
+Tambi\u{e9}n en castellano: fichar, estado, poner, exportar, barra, cerrar, mostrar, ayuda.";
+
+/// Lo que devuelve una orden: el texto a mostrar y los cambios para la auditoría.
+#[derive(Default)]
+pub struct Salida {
+    pub texto: String,
+    pub entradas: Vec<auditoria::Entrada>,
+    /// Se ha cerrado la semana: después de registrar las entradas hay que firmar un punto de
+    /// control, y la rejilla es de otra semana.
+    pub cierre: bool,
+}
+
+impl From<String> for Salida {
+    fn from(texto: String) -> Self {
+        Self {
+            texto,
+            ..Default::default()
+        }
+    }
+}
+
+/// Ejecuta la orden de `args` (sin el nombre del programa).
+pub fn ejecutar(args: &[String]) -> Result<(), String> {
+    let orden = args.first().ok_or(AYUDA)?;
+
+    match orden.as_str() {
+        "help" | "ayuda" | "-h" | "--help" => {
+            println!("{AYUDA}");
+            Ok(())
+        }
+        "api" => crate::api::sin_ventana(),
+        "bar" | "barra" if args.iter().any(|a| a == "--follow" || a == "--seguir") => seguir(args),
+        _ => {
+            print!("{}", remitir(args)?);
+            Ok(())
+        }
+    }
+}
+
+/// Manda la orden a la ventana abierta o, si no hay ninguna, la ejecuta sobre `app.ron`.
+fn remitir(args: &[String]) -> Result<String, String> {
+    if let Some(resultado) = instancia::enviar(args) {
+        return resultado;
+    }
+    if matches!(args[0].as_str(), "show" | "mostrar") {
+        return Err("Horario no est\u{e1} abierto".to_owned());
+    }
+
+    // Mientras se guarda se tiene el cerrojo, para que no se abra la ventana a medias.
+    let cerrojo =
+        instancia::cerrojo().map_err(|err| format!("No se pueden leer los datos: {err}"))?;
+    en_almacen(args, cerrojo.is_some())
+}
+
+/// Ejecuta la orden directamente sobre `app.ron`. Sin `cerrojo` sólo se puede consultar: lo
+/// tiene otra instancia que no atiende el socket.
+pub fn en_almacen(args: &[String], cerrojo: bool) -> Result<String, String> {
+    let mut almacen =
+        Almacen::abrir().map_err(|err| format!("No se pueden leer los datos: {err}"))?;
+    let mut datos = almacen.datos();
+
+    // El modo Automático, como lo aplicaría la ventana antes y después de la orden.
+    let mut entradas = estado::automatico(&mut datos);
+    let mut salida = orden(&mut datos, args)?;
+    entradas.append(&mut salida.entradas);
+    entradas.extend(estado::automatico(&mut datos));
+    salida.entradas = entradas;
+
+    if !salida.entradas.is_empty() || salida.cierre {
+        if !cerrojo {
+            return Err("Horario est\u{e1} abierto pero no responde".to_owned());
+        }
+        almacen
+            .guardar(&datos)
+            .map_err(|err| format!("No se pueden guardar los datos: {err}"))?;
+        auditoria::registrar(&salida.entradas).map_err(|err| format!("Auditor\u{ed}a: {err}"))?;
+        if salida.cierre {
+            firma::firmar().map_err(|err| format!("Punto de control: {err}"))?;
+        }
+
+        let ahora = chrono::Local::now().naive_local();
+        let enviador = webhook::Enviador::iniciar();
+        enviador.avisar(
+            &datos,
+            &webhook::fichajes(&salida.entradas, ahora.date()),
+            ahora,
+        );
+        // Un intento y poco más: el terminal no puede esperar a todos los reintentos.
+        enviador.terminar(Duration::from_secs(3));
+    }
+
+    Ok(salida.texto)
+}
+
+/// Ejecuta una orden sobre `datos`; la usan el terminal y la ventana al recibirla por el socket.
+pub fn orden(datos: &mut Datos, args: &[String]) -> Result<Salida, String> {
+    let (orden, resto) = args.split_first().ok_or(AYUDA)?;
+
+    match orden.as_str() {
+        "punch" | "fichar" => punch(datos, resto),
+        "status" | "estado" => Ok(status(datos).into()),
+        "set" | "poner" => set(datos, resto),
+        "close" | "cerrar" => Ok(cerrar(datos)),
+        "export" | "exportar" => export(datos, resto).map(Salida::from),
+        "bar" | "barra" => bar(datos, resto).map(Salida::from),
+        _ => Err(format!("Orden desconocida: {orden}\n\n{AYUDA}")),
+    }
+}
+
+/// `HHMM` o `HH:MM`.
+pub fn hhmm(texto: &str) -> Result<String, String> {
+    let texto = texto.replace(':', "");
+    NaiveTime::parse_from_str(&texto, HM)
+        .map(|t| t.format(HM).to_string())
+        .map_err(|_| format!("Hora no v\u{e1}lida: {texto} (HHMM)"))
+}
+
+fn fecha(texto: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(texto, DMY)
+        .map_err(|_| format!("Fecha no v\u{e1}lida: {texto} (DD/MM/AAAA)"))
+}
+
+fn punch(datos: &mut Datos, args: &[String]) -> Result<Salida, String> {
+    let ahora = Local::now().naive_local();
+    let ahora = match args.first() {
+        Some(hora) => {
+            let hora = NaiveTime::parse_from_str(&hhmm(hora)?, HM).unwrap_or_default();
+            NaiveDateTime::new(ahora.date(), hora)
+        }
+        None => ahora,
+    };
+
+    let entrada = estado::fichar(datos, ahora, Motivo::Terminal)?;
+    Ok(Salida {
+        texto: format!(
+            "{} {}\n",
+            FICHAJE_NAMES[entrada.celda / 5],
+            calculo::hora(&entrada.nuevo)
+        ),
+        entradas: vec![entrada],
+        cierre: false,
+    })
+}
+
+fn status(datos: &Datos) -> String {
+    let estado = estado::estado(datos, Local::now().naive_local());
+    let mut texto = String::new();
+
+    if let Some(dia) = estado.dia {
+        let _ = writeln!(
+            texto,
+            "{}:  {}{}",
+            DAYS_WEEK_NAMES[dia],
+            calculo::horas(estado.hoy),
+            if estado.dentro { "  (fichado)" } else { "" }
+        );
+    }
+    let _ = writeln!(
+        texto,
+        "Saldo semana:  {}",
+        estado::saldo_texto(estado.saldo)
+    );
+    if let Some(salida) = estado.salida {
+        let _ = writeln!(texto, "Salida prevista:  {}", salida.format("%H:%M"));
+    }
+
+    texto
+}
+
+fn set(datos: &mut Datos, args: &[String]) -> Result<Salida, String> {
+    let [dia, fichaje, valor] = args else {
+        return Err("Uso: horario set DIA FICHAJE HHMM|-".to_owned());
+    };
+
+    let dia = match dia.to_lowercase().as_str() {
+        "mon" | "lun" | "1" => 0,
+        "tue" | "mar" | "2" => 1,
+        "wed" | "mie" | "mi\u{e9}" | "3" => 2,
+        "thu" | "jue" | "4" => 3,
+        "fri" | "vie" | "5" => 4,
+        _ => return Err(format!("D\u{ed}a no v\u{e1}lido: {dia}")),
+    };
+    let fila = match fichaje.parse::<usize>() {
+        Ok(n @ 1..=4) => n - 1,
+        _ => return Err(format!("Fichaje no v\u{e1}lido: {fichaje} (1..4)")),
+    };
+    let valor = match valor.as_str() {
+        "-" => String::new(),
+        hora => hhmm(hora)?,
+    };
+
+    let entrada = estado::poner(datos, dia + fila * 5, &valor, Motivo::Terminal)?;
+    Ok(Salida {
+        texto: format!(
+            "{} {}: {}\n",
+            DAYS_WEEK_NAMES[dia],
+            FICHAJE_NAMES[fila],
+            if valor.is_empty() {
+                "vac\u{ed}o".to_owned()
+            } else {
+                calculo::hora(&valor)
+            }
+        ),
+        entradas: vec![entrada],
+        cierre: false,
+    })
+}
+
+/// Como el botón Cerrar: guarda la semana de la rejilla y pasa a la siguiente.
+fn cerrar(datos: &mut Datos) -> Salida {
+    let semana = Semana::actual(datos);
+    let previos: Vec<String> = datos
+        .fichajes
+        .iter()
+        .map(|c| c.cell.trim().to_owned())
+        .collect();
+
+    historial::cerrar(datos, crate::lunes_actual());
+
+    Salida {
+        texto: format!(
+            "Semana del {} cerrada:  {}\n",
+            semana.lunes.format(DMY),
+            estado::saldo_texto(semana.saldo)
+        ),
+        entradas: auditoria::cambios(
+            &previos,
+            &crate::fichajes_vacios(),
+            semana.lunes,
+            Motivo::Cierre,
+        ),
+        cierre: true,
+    }
+}
+
+fn export(datos: &Datos, args: &[String]) -> Result<String, String> {
+    let hoy = Local::now().date_naive();
+    let mut desde = hoy.with_day(1).unwrap_or(hoy);
+    let mut hasta = hoy;
+    let mut formato = None;
+    let mut ausencias = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--csv" | "--ics" | "--json" | "--pdf" => formato = Some(&arg[2..]),
+            "--ausencias" => ausencias = true,
+            "--desde" => desde = fecha(args.next().ok_or("Falta la fecha de --desde")?)?,
+            "--hasta" => hasta = fecha(args.next().ok_or("Falta la fecha de --hasta")?)?,
+            _ => return Err(format!("Opci\u{f3}n desconocida: {arg}")),
+        }
+    }
+
+    Ok(
+        match formato.ok_or("Indique --csv, --ics, --json o --pdf")? {
+            "csv" => csv::generar(datos, desde, hasta),
+            "ics" => ics::generar(datos, desde, hasta, ausencias),
+            "json" => copia::generar(datos).map_err(|err| err.to_string())? + "\n",
+            _ => {
+                let ruta = pdf::exportar(datos, desde, &crate::carpeta_exportar())
+                    .map_err(|err| err.to_string())?;
+                format!("{}\n", ruta.display())
+            }
+        },
+    )
+}
+
+fn formato(args: &[String]) -> Result<Formato, String> {
+    let mut formato = Formato::Waybar;
+
+    for arg in args {
+        match arg.as_str() {
+            "--waybar" => formato = Formato::Waybar,
+            "--i3blocks" => formato = Formato::I3blocks,
+            "--texto" | "--polybar" => formato = Formato::Texto,
+            _ => return Err(format!("Opci\u{f3}n desconocida: {arg}")),
+        }
+    }
+
+    Ok(formato)
+}
+
+fn bar(datos: &Datos, args: &[String]) -> Result<String, String> {
+    let formato = formato(args)?;
+    let estado = estado::estado(datos, Local::now().naive_local());
+    Ok(format!("{}\n", barra::generar(&estado, formato)))
+}
+
+/// `bar --follow`: la misma orden cada minuto, hasta que la barra cierre la tubería. Si falla una
+/// vez (datos a medio guardar, la ventana ocupada) se muestra el error y se sigue.
+fn seguir(args: &[String]) -> Result<(), String> {
+    let args: Vec<String> = args
+        .iter()
+        .filter(|a| *a != "--follow" && *a != "--seguir")
+        .cloned()
+        .collect();
+    let formato = formato(&args[1..])?;
+
+    let mut salida = std::io::stdout();
+    loop {
+        // Se vuelve a pedir cada vez para ver lo fichado desde la ventana o el terminal.
+        let texto =
+            remitir(&args).unwrap_or_else(|err| format!("{}\n", barra::error(&err, formato)));
+        if write!(salida, "{texto}")
+            .and_then(|()| salida.flush())
+            .is_err()
+        {
+            return Ok(());
+        }
+
+        let segundo = Local::now().second();
+        std::thread::sleep(Duration::from_secs(u64::from(60 - segundo.min(59))));
+    }
+}
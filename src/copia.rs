@@ -0,0 +1,160 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::auditoria::{self, Entrada, Motivo};
+use crate::{Cell, Datos};
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Copia de Seguridad JSON                                                             +++
+// +----------------------------------------------------------------------------------------------+
+
+// Fichero con todos los datos de la aplicación, para llevarlos a otro equipo o guardar una copia:
+//
+//   {
+//     "formato": "horario",
+//     "version": 1,
+//     "exportado": "2026-10-18T10:00:00+02:00",
+//     "datos": {
+//       "fichajes":    [20 x { "cell": "HHMM" }]  fila * 5 + día, filas Entrada/Salida Mañana/Tarde
+//       "config":      [ 4 x { "cell": ... }]     Saldo Semanal / 5, Obligatorio Tardes,
+//                                                 Tiempo a Recuperar, Automático ("true"/"false")
+//       "lunes":       "AAAA-MM-DD"               semana de los fichajes
+//       "historial":   [{ "lunes", "fichajes", "config", "saldo", "reglas" }]   semanas cerradas
+//       "ausencias":   { "AAAA-MM-DD": "Festivo" | "Vacaciones" | "Baja" | "Permiso" }
+//       "fin_de_semana": { "AAAA-MM-DD": minutos }   trabajado en sábado o domingo
+//       "reglas":      redondeos, descanso, franjas, ventanas, límites y extras del convenio
+//       "bolsa":       { "tope_positivo", "tope_negativo", "trimestres" }   topes en minutos
+//       "anual":       { "horas", "vacaciones" }   cómputo anual
+//       "sesion":      { "activa", "descanso", "usuario" }   actividad de la sesión
+//       "webhook":     { "url", "fichajes", "saldo", "tardes" }
+//       "bandeja":     true | false                 cerrar a la bandeja
+//       "api":         { "activa", "puerto", "token": "" }
+//       "importacion", "cabecera", "calendario": preferencias de importar/exportar
+//     }
+//   }
+//
+// El token de la API no sale en la copia: quien la tenga no puede usarlo, y al sustituir los datos
+// se conserva el del equipo.
+//
+// Los campos que falten toman su valor por defecto, así que una copia antigua sigue siendo válida
+// al añadir campos nuevos; `version` sólo cambia si un campo existente cambia de significado.
+// Desde la versión 1 sólo se han añadido campos (de `fin_de_semana` en adelante y `reglas` en cada
+// semana) y un programa anterior se salta los que no conoce, así que sigue siendo la 1.
+
+pub const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Copia {
+    formato: String,
+    version: u32,
+    exportado: DateTime<Local>,
+    datos: Datos,
+}
+
+pub fn generar(datos: &Datos) -> serde_json::Result<String> {
+    let mut datos = datos.clone();
+    datos.api.token.clear();
+
+    serde_json::to_string_pretty(&Copia {
+        formato: "horario".to_owned(),
+        version: VERSION,
+        exportado: Local::now(),
+        datos,
+    })
+}
+
+/// Escribe todos los datos en `dir` y devuelve la ruta del fichero.
+pub fn exportar(datos: &Datos, dir: &Path) -> io::Result<PathBuf> {
+    let ruta = dir.join(format!(
+        "horario-copia-{}.json",
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    std::fs::write(&ruta, generar(datos)?)?;
+
+    Ok(ruta)
+}
+
+/// Lee una copia y comprueba que se puede usar en esta versión.
+pub fn leer(texto: &str) -> Result<Datos, String> {
+    let copia: Copia = serde_json::from_str(texto).map_err(|err| err.to_string())?;
+
+    if copia.formato != "horario" {
+        return Err("No es una copia de Horario".to_owned());
+    }
+    if copia.version > VERSION {
+        return Err(format!("Versi\u{f3}n {} no soportada", copia.version));
+    }
+    if copia.datos.fichajes.len() != 20
+        || copia.datos.config.len() != 4
+        || copia.datos.historial.iter().any(|s| s.fichajes.len() != 20)
+    {
+        return Err("Copia incompleta".to_owned());
+    }
+
+    Ok(copia.datos)
+}
+
+/// Entradas de auditoría de las casillas que cambian al pasar de `antes` a `despues`, tanto en
+/// la rejilla como en el historial.
+pub fn cambios(antes: &Datos, despues: &Datos, motivo: Motivo) -> Vec<Entrada> {
+    let semanas = |datos: &Datos| {
+        let mut semanas: BTreeMap<NaiveDate, Vec<Cell>> = datos
+            .historial
+            .iter()
+            .map(|s| (s.lunes, s.fichajes.clone()))
+            .collect();
+        semanas.insert(
+            datos.lunes.unwrap_or_else(crate::lunes_actual),
+            datos.fichajes.clone(),
+        );
+        semanas
+    };
+    let (antes, despues) = (semanas(antes), semanas(despues));
+    let vacia = crate::fichajes_vacios();
+
+    let mut entradas = Vec::new();
+    for lunes in antes.keys().chain(despues.keys()).collect::<BTreeSet<_>>() {
+        let previos: Vec<String> = antes
+            .get(lunes)
+            .unwrap_or(&vacia)
+            .iter()
+            .map(|c| c.cell.trim().to_owned())
+            .collect();
+        let fichajes = despues.get(lunes).unwrap_or(&vacia);
+        entradas.extend(auditoria::cambios(&previos, fichajes, *lunes, motivo));
+    }
+    entradas
+}
+
+/// Añade lo que falte en `datos` sin tocar lo que ya hay: semanas del historial, ausencias, fines
+/// de semana y, si es de otra semana, la semana en curso de la copia. Devuelve las entradas para
+/// la auditoría.
+pub fn combinar(datos: &mut Datos, copia: Datos) -> Vec<Entrada> {
+    let previo = datos.clone();
+    let lunes = datos.lunes.unwrap_or_else(crate::lunes_actual);
+
+    let mut semanas = copia.historial.clone();
+    if copia.lunes.is_some_and(|l| l != lunes) {
+        semanas.push(crate::historial::Semana::actual(&copia));
+    }
+
+    for semana in semanas {
+        if semana.lunes != lunes && !datos.historial.iter().any(|s| s.lunes == semana.lunes) {
+            datos.historial.push(semana);
+        }
+    }
+    datos.historial.sort_by_key(|s| s.lunes);
+
+    for (fecha, ausencia) in copia.ausencias {
+        datos.ausencias.entry(fecha).or_insert(ausencia);
+    }
+    for (fecha, minutos) in copia.fin_de_semana {
+        datos.fin_de_semana.entry(fecha).or_insert(minutos);
+    }
+
+    cambios(&previo, datos, Motivo::Importacion)
+}
@@ -0,0 +1,515 @@
+use std::fmt::Write as _;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+use crate::auditoria::{Entrada, Motivo};
+use crate::calculo::{self, hhmm, hora};
+use crate::historial::{self, Semana};
+use crate::legal::{self, Aviso};
+use crate::{Datos, DAYS_WEEK_NAMES, HM};
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Exportar CSV                                                                        +++
+// +----------------------------------------------------------------------------------------------+
+
+// Separador `;` y horas como `H:MM`, que es lo que espera una hoja de cálculo en castellano.
+//
+// El total de cada día es lo fichado: un día sin fichajes cuenta 0 y los días después de hoy no
+// salen. El saldo es el de la aplicación, en el que un día sin fichajes cuenta la jornada. Si
+// están los cinco días, el Tiempo a Recuperar y las extraordinarias van en filas propias antes de
+// la de la semana, que suma la columna del saldo: así coincide con el saldo de la semana.
+
+const CABECERA: &str =
+    "fecha;dia;entrada;salida;entrada tarde;salida tarde;total;objetivo;saldo;ausencia;avisos";
+
+fn en_rango(fecha: NaiveDate, desde: NaiveDate, hasta: NaiveDate) -> bool {
+    desde <= fecha && fecha <= hasta
+}
+
+/// Textos de los avisos legales de un día, o de la semana con `None`.
+fn avisos_csv(avisos: &[Aviso], dia: Option<usize>) -> String {
+    avisos
+        .iter()
+        .filter(|a| a.dia() == dia)
+        .map(Aviso::texto)
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+/// Filas de una semana: una por día dentro del rango y un resumen de esos días.
+fn semana_csv(
+    csv: &mut String,
+    semana: &Semana,
+    avisos: &[Aviso],
+    desde: NaiveDate,
+    hasta: NaiveDate,
+) {
+    let resumen = semana.resumen();
+    let parcial = semana.parcial(desde, hasta);
+    let fichajes = &semana.fichajes;
+    let mut total = 0;
+    let mut objetivo = 0;
+
+    for dia in 0..5 {
+        let fecha = semana.fecha(dia);
+        if !en_rango(fecha, desde, hasta) {
+            continue;
+        }
+
+        let trabajado = semana.trabajado(dia);
+        total += trabajado;
+        objetivo += resumen.objetivos[dia];
+
+        let _ = writeln!(
+            csv,
+            "{};{};{};{};{};{};{};{};{};{};{}",
+            fecha.format("%d/%m/%Y"),
+            DAYS_WEEK_NAMES[dia],
+            hora(&fichajes[dia].cell),
+            hora(&fichajes[dia + 5].cell),
+            hora(&fichajes[dia + 10].cell),
+            hora(&fichajes[dia + 15].cell),
+            hhmm(trabajado),
+            hhmm(resumen.objetivos[dia]),
+            hhmm(resumen.dias[dia] - resumen.objetivos[dia]),
+            semana.ausencia(dia),
+            avisos_csv(avisos, Some(dia))
+        );
+    }
+
+    let lunes = semana.lunes.format("%d/%m/%Y");
+    if parcial.recuperar != 0 {
+        objetivo += parcial.recuperar;
+        let _ = writeln!(
+            csv,
+            "{lunes};Recuperar;;;;;;{};{};;",
+            hhmm(parcial.recuperar),
+            hhmm(-parcial.recuperar)
+        );
+    }
+    if parcial.extras != 0 {
+        let _ = writeln!(csv, "{lunes};Extras;;;;;;;{};;", hhmm(-parcial.extras));
+    }
+
+    let _ = writeln!(
+        csv,
+        "{lunes};Semana;;;;;{};{};{};;{}",
+        hhmm(total),
+        hhmm(objetivo),
+        hhmm(parcial.saldo),
+        avisos_csv(avisos, None)
+    );
+}
+
+pub fn generar(datos: &Datos, desde: NaiveDate, hasta: NaiveDate) -> String {
+    let hasta = hasta.min(chrono::Local::now().date_naive());
+    let mut csv = String::new();
+    let _ = writeln!(csv, "{CABECERA}");
+
+    let semanas = historial::semanas(datos);
+    for (n, semana) in semanas.iter().enumerate() {
+        if (0..5).any(|dia| en_rango(semana.fecha(dia), desde, hasta)) {
+            let avisos = legal::avisos(&semanas, n);
+            semana_csv(&mut csv, semana, &avisos, desde, hasta);
+        }
+    }
+
+    csv
+}
+
+/// Escribe el CSV del rango en `dir` y devuelve la ruta del fichero.
+pub fn exportar(
+    datos: &Datos,
+    desde: NaiveDate,
+    hasta: NaiveDate,
+    dir: &Path,
+) -> io::Result<PathBuf> {
+    let ruta = dir.join(format!(
+        "horario-{}-{}.csv",
+        desde.format("%Y%m%d"),
+        hasta.format("%Y%m%d")
+    ));
+    std::fs::write(&ruta, generar(datos, desde, hasta))?;
+
+    Ok(ruta)
+}
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Importar CSV                                                                        +++
+// +----------------------------------------------------------------------------------------------+
+
+// Fichadas de los terminales de acceso (empleado, fecha y hora, sentido). Las columnas empiezan en
+// 1; una columna 0 significa que el fichero no la trae.
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Importacion {
+    pub separador: String,
+    pub cabecera: bool,
+    pub col_empleado: usize,
+    /// Identificador del empleado a importar; vacío = todas las filas.
+    pub empleado: String,
+    pub col_fecha: usize,
+    /// Columna con la hora, si no viene junto a la fecha.
+    pub col_hora: usize,
+    /// Formato `chrono` de la fecha (y hora, si van juntas).
+    pub formato: String,
+    /// Columna con el sentido; sin ella las fichadas se alternan entrada/salida.
+    pub col_sentido: usize,
+    pub entrada: String,
+    pub salida: String,
+}
+
+impl Default for Importacion {
+    fn default() -> Self {
+        Self {
+            separador: ";".to_owned(),
+            cabecera: true,
+            col_empleado: 1,
+            empleado: String::new(),
+            col_fecha: 2,
+            col_hora: 0,
+            formato: "%d/%m/%Y %H:%M:%S".to_owned(),
+            col_sentido: 3,
+            entrada: "E".to_owned(),
+            salida: "S".to_owned(),
+        }
+    }
+}
+
+/// Un fichaje leído del fichero, pendiente de que el usuario lo acepte.
+#[derive(Clone, Debug)]
+pub struct Propuesta {
+    pub fecha: NaiveDate,
+    /// Posición en la rejilla (fila * 5 + día).
+    pub celda: usize,
+    pub anterior: String,
+    pub nuevo: String,
+    pub aceptar: bool,
+    /// De dónde sale, para la auditoría.
+    pub motivo: Motivo,
+}
+
+impl Propuesta {
+    /// El día ya tenía otro valor en esa casilla.
+    pub fn conflicto(&self) -> bool {
+        !self.anterior.is_empty()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sentido {
+    Entrada,
+    Salida,
+}
+
+fn campo<'a>(campos: &[&'a str], col: usize) -> Option<&'a str> {
+    match col {
+        0 => None,
+        n => campos.get(n - 1).map(|c| c.trim().trim_matches('"').trim()),
+    }
+}
+
+/// Fichadas del empleado configurado, o el número de línea que no se pudo leer.
+pub fn leer_fichadas(
+    texto: &str,
+    imp: &Importacion,
+) -> Result<Vec<(NaiveDateTime, Option<Sentido>)>, String> {
+    let separador = imp.separador.chars().next().unwrap_or(';');
+    let mut fichadas = Vec::new();
+
+    for (n, linea) in texto.lines().enumerate() {
+        if linea.trim().is_empty() || (imp.cabecera && n == 0) {
+            continue;
+        }
+
+        let campos: Vec<&str> = linea.split(separador).collect();
+
+        if !imp.empleado.trim().is_empty()
+            && campo(&campos, imp.col_empleado) != Some(imp.empleado.trim())
+        {
+            continue;
+        }
+
+        let fecha = match (campo(&campos, imp.col_fecha), campo(&campos, imp.col_hora)) {
+            (Some(fecha), Some(hora)) => format!("{fecha} {hora}"),
+            (Some(fecha), None) => fecha.to_owned(),
+            _ => return Err(format!("L\u{ed}nea {}: falta la fecha", n + 1)),
+        };
+        let fecha = NaiveDateTime::parse_from_str(&fecha, &imp.formato)
+            .map_err(|_| format!("L\u{ed}nea {}: fecha no v\u{e1}lida \"{fecha}\"", n + 1))?;
+
+        let sentido = match campo(&campos, imp.col_sentido) {
+            Some(s) if s.eq_ignore_ascii_case(imp.entrada.trim()) => Some(Sentido::Entrada),
+            Some(s) if s.eq_ignore_ascii_case(imp.salida.trim()) => Some(Sentido::Salida),
+            Some(s) => return Err(format!("L\u{ed}nea {}: sentido desconocido \"{s}\"", n + 1)),
+            None => None,
+        };
+
+        fichadas.push((fecha, sentido));
+    }
+
+    fichadas.sort_by_key(|(fecha, _)| *fecha);
+    Ok(fichadas)
+}
+
+/// Reparte las fichadas de un día en las cuatro casillas Entrada/Salida Mañana/Tarde. Si falta
+/// una fichada se deja su casilla vacía; lo que no cabe se descarta.
+fn casillas(fichadas: &[(NaiveTime, Option<Sentido>)]) -> [Option<NaiveTime>; 4] {
+    let mut casillas = [None; 4];
+    let mut pos = 0;
+
+    for (hora, sentido) in fichadas {
+        let esperado = if pos % 2 == 0 {
+            Sentido::Entrada
+        } else {
+            Sentido::Salida
+        };
+        if sentido.is_some_and(|s| s != esperado) {
+            pos += 1;
+        }
+        if pos >= 4 {
+            break;
+        }
+        casillas[pos] = Some(*hora);
+        pos += 1;
+    }
+
+    casillas
+}
+
+/// Cambios que supondría importar las fichadas. Los sábados y domingos no tienen casilla y se
+/// ignoran.
+pub fn propuestas(
+    datos: &Datos,
+    fichadas: &[(NaiveDateTime, Option<Sentido>)],
+    motivo: Motivo,
+) -> Vec<Propuesta> {
+    let semanas = historial::semanas(datos);
+    let mut propuestas = Vec::new();
+
+    let mut dias: Vec<NaiveDate> = fichadas.iter().map(|(f, _)| f.date()).collect();
+    dias.dedup();
+
+    for fecha in dias {
+        let dia = fecha.weekday().num_days_from_monday() as usize;
+        if dia > 4 {
+            continue;
+        }
+
+        let del_dia: Vec<(NaiveTime, Option<Sentido>)> = fichadas
+            .iter()
+            .filter(|(f, _)| f.date() == fecha)
+            .map(|(f, s)| (f.time(), *s))
+            .collect();
+
+        let lunes = fecha - Duration::days(dia as i64);
+        let semana = semanas.iter().find(|s| s.lunes == lunes);
+
+        for (fila, hora) in casillas(&del_dia).iter().enumerate() {
+            let Some(hora) = hora else { continue };
+
+            let celda = fila * 5 + dia;
+            let anterior = semana
+                .map(|s| s.fichajes[celda].cell.trim().to_owned())
+                .unwrap_or_default();
+            let nuevo = hora.format(HM).to_string();
+
+            if anterior != nuevo {
+                propuestas.push(Propuesta {
+                    fecha,
+                    celda,
+                    aceptar: anterior.is_empty(),
+                    anterior,
+                    nuevo,
+                    motivo,
+                });
+            }
+        }
+    }
+
+    propuestas
+}
+
+/// Aplica las propuestas aceptadas, en la rejilla si son de la semana actual o en el historial
+/// si no, y devuelve las entradas para la auditoría. Las de semanas posteriores a la de la
+/// rejilla pasan a ella al cerrar la semana (`historial::cerrar`).
+///
+/// Los días que quedarían con los fichajes desordenados no se tocan y se devuelven aparte.
+pub fn aplicar(datos: &mut Datos, propuestas: &[Propuesta]) -> (Vec<Entrada>, Vec<NaiveDate>) {
+    let actual = datos.lunes.unwrap_or_else(crate::lunes_actual);
+    let mut entradas = Vec::new();
+    let mut desordenados = Vec::new();
+
+    let mut dias: Vec<NaiveDate> = propuestas
+        .iter()
+        .filter(|p| p.aceptar)
+        .map(|p| p.fecha)
+        .collect();
+    dias.sort();
+    dias.dedup();
+
+    for fecha in dias {
+        let del_dia: Vec<&Propuesta> = propuestas
+            .iter()
+            .filter(|p| p.aceptar && p.fecha == fecha)
+            .collect();
+        let dia = fecha.weekday().num_days_from_monday() as usize;
+        let lunes = fecha - Duration::days(dia as i64);
+
+        let mut fichajes = if lunes == actual {
+            datos.fichajes.clone()
+        } else {
+            match datos.historial.iter().find(|s| s.lunes == lunes) {
+                Some(semana) => semana.fichajes.clone(),
+                None => crate::fichajes_vacios(),
+            }
+        };
+        for propuesta in &del_dia {
+            fichajes[propuesta.celda].cell = format!("      {}", propuesta.nuevo);
+        }
+        if !calculo::orden_valido(&fichajes, dia) {
+            desordenados.push(fecha);
+            continue;
+        }
+
+        if lunes == actual {
+            datos.fichajes = fichajes;
+        } else {
+            let ausencias = historial::ausencias(datos, lunes);
+            let fin_de_semana = historial::fin_de_semana(datos, lunes);
+            if !datos.historial.iter().any(|s| s.lunes == lunes) {
+                datos.historial.push(Semana {
+                    lunes,
+                    fichajes: crate::fichajes_vacios(),
+                    config: datos.config.clone(),
+                    saldo: 0,
+                    reglas: datos.reglas.clone(),
+                    ausencias,
+                    fin_de_semana,
+                });
+            }
+            if let Some(semana) = datos.historial.iter_mut().find(|s| s.lunes == lunes) {
+                semana.fichajes = fichajes;
+                semana.ausencias = ausencias;
+                semana.fin_de_semana = fin_de_semana;
+                semana.saldo = semana.resumen().saldo();
+            }
+        }
+
+        entradas.extend(del_dia.iter().map(|propuesta| {
+            Entrada::nueva(
+                propuesta.fecha,
+                propuesta.celda,
+                &propuesta.anterior,
+                &propuesta.nuevo,
+                propuesta.motivo,
+            )
+        }));
+    }
+
+    datos.historial.sort_by_key(|s| s.lunes);
+    (entradas, desordenados)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fecha(mes: u32, dia: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, mes, dia).unwrap()
+    }
+
+    fn propuesta(fecha: NaiveDate, fila: usize, nuevo: &str) -> Propuesta {
+        let dia = fecha.weekday().num_days_from_monday() as usize;
+        Propuesta {
+            fecha,
+            celda: fila * 5 + dia,
+            anterior: String::new(),
+            nuevo: nuevo.to_owned(),
+            aceptar: true,
+            motivo: Motivo::Importacion,
+        }
+    }
+
+    #[test]
+    fn aplicar_no_toca_los_dias_que_quedarian_desordenados() {
+        let mut datos = Datos {
+            lunes: Some(fecha(10, 12)),
+            ..Datos::default()
+        };
+        datos.fichajes[0].cell = "      0800".to_owned();
+        datos.fichajes[5].cell = "      1400".to_owned();
+
+        let propuestas = [
+            // Lunes: la entrada de la tarde antes de la salida de la mañana.
+            propuesta(fecha(10, 12), 2, "1300"),
+            // Martes, en la rejilla, y el lunes anterior, en el historial: en orden.
+            propuesta(fecha(10, 13), 0, "0900"),
+            propuesta(fecha(10, 13), 1, "1500"),
+            propuesta(fecha(10, 5), 0, "0800"),
+            propuesta(fecha(10, 5), 1, "1400"),
+            // Martes anterior, sólo en el fichero: desordenado.
+            propuesta(fecha(10, 6), 0, "1500"),
+            propuesta(fecha(10, 6), 1, "0900"),
+        ];
+        let (entradas, desordenados) = aplicar(&mut datos, &propuestas);
+
+        assert_eq!(desordenados, [fecha(10, 6), fecha(10, 12)]);
+        assert_eq!(entradas.len(), 4);
+        assert!(datos.fichajes[10].cell.is_empty());
+        assert_eq!(datos.fichajes[1].cell.trim(), "0900");
+        assert_eq!(datos.fichajes[6].cell.trim(), "1500");
+
+        let semana = &datos.historial[0];
+        assert_eq!(semana.lunes, fecha(10, 5));
+        assert_eq!(semana.fichajes[0].cell.trim(), "0800");
+        assert!(semana.fichajes[1].cell.is_empty());
+        assert!(semana.fichajes[6].cell.is_empty());
+    }
+
+    /// Segundos de un `H:MM` con signo, como los escribe `hhmm`.
+    fn segundos(texto: &str) -> i64 {
+        let (signo, texto) = match texto.strip_prefix('-') {
+            Some(resto) => (-1, resto),
+            None => (1, texto),
+        };
+        let (h, m) = texto.split_once(':').unwrap();
+        signo * (h.parse::<i64>().unwrap() * 3600 + m.parse::<i64>().unwrap() * 60)
+    }
+
+    #[test]
+    fn la_fila_de_la_semana_suma_los_saldos_y_es_el_de_la_semana() {
+        let mut datos = Datos {
+            lunes: Some(fecha(3, 9)),
+            ..Datos::default()
+        };
+        datos.config[2].cell = "      0100".to_owned();
+        datos.reglas.extras.diaria = 8 * 60;
+        // Lunes largo, martes a medias, miércoles sin fichajes y jueves y viernes normales.
+        for (dia, salida) in [(0, "1800"), (1, "1100"), (3, "1530"), (4, "1530")] {
+            datos.fichajes[dia].cell = "      0800".to_owned();
+            datos.fichajes[dia + 5].cell = format!("      {salida}");
+        }
+        historial::archivar(&mut datos);
+
+        let csv = generar(&datos, fecha(3, 9), fecha(3, 13));
+        let filas: Vec<Vec<&str>> = csv
+            .lines()
+            .skip(1)
+            .map(|l| l.split(';').collect())
+            .collect();
+        let (semana, resto) = filas.split_last().unwrap();
+
+        assert_eq!(semana[1], "Semana");
+        assert_eq!(resto.len(), 7);
+        assert_eq!(resto[5][1], "Recuperar");
+        assert_eq!(resto[6][1], "Extras");
+        let suma: i64 = resto.iter().map(|f| segundos(f[8])).sum();
+        assert_eq!(segundos(semana[8]), suma);
+        assert_eq!(suma, datos.historial[0].saldo);
+    }
+}
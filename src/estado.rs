@@ -0,0 +1,211 @@
+use chrono::{Datelike, Duration, NaiveDateTime, NaiveTime, Timelike};
+
+use crate::auditoria::{self, Entrada, Motivo};
+use crate::calculo;
+use crate::historial::Semana;
+use crate::Datos;
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Estado                                                                              +++
+// +----------------------------------------------------------------------------------------------+
+
+// Situación en un momento dado, contando el tramo abierto si se ha fichado la entrada y aún no la
+// salida. Es lo que muestran el terminal y las integraciones sin abrir la ventana.
+
+#[derive(Clone, Debug, Default)]
+pub struct Estado {
+    /// Día de hoy en la rejilla (0 = Lunes), si la rejilla es de esta semana y no es fin de semana.
+    pub dia: Option<usize>,
+    /// Hay una entrada sin salida.
+    pub dentro: bool,
+    /// Tiempo trabajado hoy, en segundos.
+    pub hoy: i64,
+    /// Saldo de la semana, en segundos.
+    pub saldo: i64,
+    /// Tiempo fichado por las tardes en la semana, en segundos.
+    pub tardes: i64,
+    /// Hora a la que el saldo llega a cero si se sigue trabajando.
+    pub salida: Option<NaiveTime>,
+}
+
+/// Día de la rejilla que corresponde a `ahora`.
+pub fn dia(datos: &Datos, ahora: NaiveDateTime) -> Option<usize> {
+    let lunes = datos.lunes.unwrap_or_else(crate::lunes_actual);
+    let dia = (ahora.date() - lunes).num_days();
+    (0..5).contains(&dia).then_some(dia as usize)
+}
+
+/// Salida del viernes del modo Automático: la hora a la que se completa el objetivo de la semana
+/// entrando a la de la Entrada Mañana del viernes. `None` si el modo está apagado, no hay entrada
+/// el viernes o la salida no cabe en el día.
+///
+/// Se busca minuto a minuto: con las reglas, el viernes computado no crece al mismo ritmo que el
+/// reloj (descanso, redondeo de la salida, ventana de fichaje).
+#[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+pub fn salida_automatica(datos: &Datos) -> Option<NaiveTime> {
+    if datos.config[3].cell != "true" || datos.fichajes[4].cell.trim().is_empty() {
+        return None;
+    }
+
+    let mut semana = Semana::actual(datos);
+    for celda in [9, 14, 19] {
+        semana.fichajes[celda].cell.clear();
+    }
+    let resumen = semana.resumen();
+    // Las extraordinarias de la semana no cuentan para el objetivo.
+    let falta = resumen.objetivo - (resumen.total - resumen.extra() - resumen.viernes);
+    if falta <= 0 {
+        return None;
+    }
+
+    let entrada = calculo::segundos(&semana.fichajes[4].cell) / 60;
+    (entrada + 1..24 * 60)
+        .find(|minuto| {
+            semana.fichajes[9].cell = format!("      {:02}{:02}", minuto / 60, minuto % 60);
+            calculo::fichado(&semana.fichajes, 4, &semana.reglas) >= falta
+        })
+        .and_then(|minuto| NaiveTime::from_num_seconds_from_midnight_opt(minuto as u32 * 60, 0))
+}
+
+/// Con el modo Automático, pone en la Salida Mañana del viernes la salida prevista y deja vacía
+/// la tarde del viernes, como hace la ventana en cada repintado. Devuelve las entradas para la
+/// auditoría.
+pub fn automatico(datos: &mut Datos) -> Vec<Entrada> {
+    if datos.config[3].cell != "true" {
+        return Vec::new();
+    }
+
+    let previos: Vec<String> = datos
+        .fichajes
+        .iter()
+        .map(|c| c.cell.trim().to_owned())
+        .collect();
+    if !datos.fichajes[4].cell.trim().is_empty() {
+        datos.fichajes[14].cell.clear();
+        datos.fichajes[19].cell.clear();
+    }
+    datos.fichajes[9].cell = salida_automatica(datos)
+        .map(|salida| salida.format("      %H%M").to_string())
+        .unwrap_or_default();
+
+    let lunes = datos.lunes.unwrap_or_else(crate::lunes_actual);
+    auditoria::cambios(&previos, &datos.fichajes, lunes, Motivo::Automatico)
+}
+
+/// Con el modo Automático, el viernes se está dentro hasta la salida prevista.
+pub fn estado(datos: &Datos, ahora: NaiveDateTime) -> Estado {
+    match salida_automatica(datos) {
+        Some(salida) if dia(datos, ahora) == Some(4) && ahora.time() < salida => {
+            let mut datos = datos.clone();
+            datos.fichajes[9].cell.clear();
+            Estado {
+                salida: Some(salida),
+                ..calcular(&datos, ahora)
+            }
+        }
+        _ => calcular(datos, ahora),
+    }
+}
+
+fn calcular(datos: &Datos, ahora: NaiveDateTime) -> Estado {
+    let resumen = Semana::actual(datos).resumen();
+    let mut estado = Estado {
+        saldo: resumen.saldo(),
+        tardes: resumen.tardes,
+        ..Default::default()
+    };
+
+    let Some(dia) = dia(datos, ahora) else {
+        return estado;
+    };
+    estado.dia = Some(dia);
+
+    let f = |fila: usize| datos.fichajes[dia + fila * 5].cell.trim();
+    let (entrada, tarde) = if !f(0).is_empty() && f(1).is_empty() && f(2).is_empty() {
+        (Some(f(0)), false)
+    } else if !f(2).is_empty() && f(3).is_empty() {
+        (Some(f(2)), true)
+    } else {
+        (None, false)
+    };
+
+    let segundos = ahora.time().num_seconds_from_midnight() as i64;
+    let reglas = &datos.reglas;
+    let abierto = entrada.map_or(0, |e| {
+        (reglas.ventanas[dia].recortar(segundos) - reglas.entrada(dia, calculo::segundos(e))).max(0)
+    });
+
+    estado.dentro = entrada.is_some();
+    estado.hoy = calculo::fichado(&datos.fichajes, dia, &datos.reglas) + abierto;
+    estado.saldo += abierto;
+    if tarde {
+        estado.tardes += abierto;
+    }
+    if estado.dentro {
+        estado.salida = Some(ahora.time() + Duration::seconds((-estado.saldo).max(0)));
+    }
+
+    estado
+}
+
+/// Saldo con el mismo signo [ ⛶ | ➖ | ➕ ] que la ventana.
+pub fn saldo_texto(saldo: i64) -> String {
+    format!("{}  {}", crate::color_saldo(saldo).1, calculo::horas(saldo))
+}
+
+/// Pone `valor` (`HHMM`, vacío para borrar) en una casilla de la rejilla, si respeta el orden de
+/// los tramos del día, y devuelve la entrada para la auditoría.
+pub fn poner(
+    datos: &mut Datos,
+    celda: usize,
+    valor: &str,
+    motivo: Motivo,
+) -> Result<Entrada, String> {
+    let lunes = datos.lunes.unwrap_or_else(crate::lunes_actual);
+    let dia = celda % 5;
+    let anterior = datos.fichajes[celda].cell.trim().to_owned();
+
+    let mut fichajes = datos.fichajes.clone();
+    fichajes[celda].cell = format!("      {valor}");
+    if !calculo::orden_valido(&fichajes, dia) {
+        return Err(format!(
+            "{} {} no respeta el orden de los fichajes del d\u{ed}a",
+            crate::FICHAJE_NAMES[celda / 5],
+            calculo::hora(valor)
+        ));
+    }
+
+    datos.fichajes = fichajes;
+    Ok(Entrada::nueva(
+        lunes + Duration::days(dia as i64),
+        celda,
+        &anterior,
+        valor,
+        motivo,
+    ))
+}
+
+/// Ficha `ahora` en la primera casilla libre del día.
+pub fn fichar(datos: &mut Datos, ahora: NaiveDateTime, motivo: Motivo) -> Result<Entrada, String> {
+    let Some(dia) = dia(datos, ahora) else {
+        let lunes = datos.lunes.unwrap_or_else(crate::lunes_actual);
+        return Err(if ahora.date().weekday().num_days_from_monday() > 4 {
+            "Hoy no es laborable".to_owned()
+        } else {
+            format!(
+                "Los fichajes son de la semana del {}; ci\u{e9}rrela antes",
+                lunes.format(crate::DMY)
+            )
+        });
+    };
+
+    let celda = (0..4)
+        .map(|fila| dia + fila * 5)
+        .find(|&celda| datos.fichajes[celda].cell.trim().is_empty())
+        .ok_or_else(|| "Ya hay cuatro fichajes hoy".to_owned())?;
+    if dia == 4 && celda != 4 && datos.config[3].cell == "true" {
+        return Err("El viernes la salida la pone el modo Autom\u{e1}tico".to_owned());
+    }
+
+    poner(datos, celda, &ahora.format(crate::HM).to_string(), motivo)
+}
@@ -0,0 +1,164 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+use crate::auditoria::{self, Entrada};
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Puntos de Control                                                                   +++
+// +----------------------------------------------------------------------------------------------+
+
+// Un punto de control es el hash de la última entrada del registro firmado con una clave Ed25519
+// propia del equipo. Se crea uno al cerrar cada semana y otro cada vez que se exporta, de forma
+// que RR.HH. pueda comprobar más adelante que el registro no se ha reescrito desde entonces.
+
+const CLAVE: &str = "clave.ed25519";
+const FICHERO: &str = "puntos_control.jsonl";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PuntoControl {
+    pub fecha: DateTime<Local>,
+    /// Número de entradas del registro cubiertas por la firma.
+    pub entradas: usize,
+    /// Hash de la última entrada cubierta.
+    pub hash: String,
+    /// Clave pública Ed25519 (hex).
+    pub clave: String,
+    /// Firma Ed25519 (hex) de `mensaje()`.
+    pub firma: String,
+}
+
+impl PuntoControl {
+    pub fn mensaje(&self) -> String {
+        format!(
+            "horario|{}|{}|{}",
+            self.fecha.with_timezone(&Utc).to_rfc3339(),
+            self.entradas,
+            self.hash
+        )
+    }
+
+    pub fn firma_valida(&self) -> bool {
+        let clave = bytes(&self.clave)
+            .and_then(|b| b.try_into().ok())
+            .and_then(|b: [u8; 32]| VerifyingKey::from_bytes(&b).ok());
+        let firma = bytes(&self.firma)
+            .and_then(|b| b.try_into().ok())
+            .map(|b: [u8; 64]| Signature::from_bytes(&b));
+
+        match (clave, firma) {
+            (Some(clave), Some(firma)) => clave
+                .verify_strict(self.mensaje().as_bytes(), &firma)
+                .is_ok(),
+            _ => false,
+        }
+    }
+}
+
+fn bytes(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn ruta(fichero: &str) -> io::Result<PathBuf> {
+    let dir =
+        crate::data_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "data_dir"))?;
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir.join(fichero))
+}
+
+/// Clave de firma del equipo; se genera la primera vez que se necesita.
+fn clave() -> io::Result<SigningKey> {
+    let ruta = ruta(CLAVE)?;
+
+    if let Ok(b) = std::fs::read(&ruta) {
+        let b: [u8; 32] = b
+            .try_into()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, CLAVE))?;
+        return Ok(SigningKey::from_bytes(&b));
+    }
+
+    let mut b = [0u8; 32];
+    getrandom::getrandom(&mut b).map_err(io::Error::other)?;
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(&ruta)?.write_all(&b)?;
+
+    Ok(SigningKey::from_bytes(&b))
+}
+
+/// Firma el estado actual del registro y lo añade a los puntos de control.
+pub fn firmar() -> io::Result<PuntoControl> {
+    let entradas = auditoria::leer();
+    let clave = clave()?;
+
+    let mut punto = PuntoControl {
+        fecha: Local::now(),
+        entradas: entradas.len(),
+        hash: entradas.last().map(|e| e.hash.clone()).unwrap_or_default(),
+        clave: auditoria::hex(clave.verifying_key().as_bytes()),
+        firma: String::new(),
+    };
+    punto.firma = auditoria::hex(&clave.sign(punto.mensaje().as_bytes()).to_bytes());
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(ruta(FICHERO)?)?;
+    writeln!(file, "{}", serde_json::to_string(&punto)?)?;
+    file.sync_all()?;
+
+    Ok(punto)
+}
+
+pub fn leer() -> Vec<PuntoControl> {
+    let Some(file) = ruta(FICHERO).ok().and_then(|ruta| File::open(ruta).ok()) else {
+        return Vec::new();
+    };
+
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|linea| serde_json::from_str(&linea).ok())
+        .collect()
+}
+
+/// Primer punto de control que no encaja con el registro: firma no válida, o el registro ya no
+/// contiene la entrada firmada.
+pub fn comprobar<'a>(puntos: &'a [PuntoControl], entradas: &[Entrada]) -> Option<&'a PuntoControl> {
+    puntos.iter().find(|punto| {
+        let hash = match punto.entradas {
+            0 => Some(String::new()),
+            n => entradas.get(n - 1).map(|e| e.hash.clone()),
+        };
+        !punto.firma_valida() || hash.as_deref() != Some(punto.hash.as_str())
+    })
+}
+
+/// Crea un punto de control nuevo y lo guarda como JSON en `dir` para entregarlo a RR.HH.
+pub fn exportar(dir: &Path) -> io::Result<PathBuf> {
+    let punto = firmar()?;
+
+    let ruta = dir.join(format!(
+        "horario-punto-control-{}.json",
+        punto.fecha.format("%Y%m%d-%H%M%S")
+    ));
+    std::fs::write(&ruta, serde_json::to_string_pretty(&punto)?)?;
+
+    Ok(ruta)
+}
@@ -0,0 +1,288 @@
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::calculo::{self, Ausencia, Resumen};
+use crate::reglas::Reglas;
+use crate::{Cell, Datos};
+
+/// Semana cerrada con el botón [ ↺ ], tal y como estaba al cerrarla.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Semana {
+    pub lunes: NaiveDate,
+    pub fichajes: Vec<Cell>,
+    pub config: Vec<Cell>,
+    /// Saldo final en segundos (negativo = pendiente).
+    pub saldo: i64,
+    /// Reglas de cómputo al cerrarla; las semanas anteriores a las reglas no tienen ninguna.
+    #[serde(default)]
+    pub reglas: Reglas,
+    /// Ausencias de cada día, copiadas de `Datos::ausencias` por `semanas`.
+    #[serde(skip)]
+    pub ausencias: [Option<Ausencia>; 5],
+    /// Segundos del sábado y el domingo, copiados de `Datos::fin_de_semana` por `semanas`.
+    #[serde(skip)]
+    pub fin_de_semana: i64,
+}
+
+impl Semana {
+    /// La semana en curso, como quedaría si se cerrase ahora.
+    pub fn actual(datos: &Datos) -> Self {
+        let lunes = datos.lunes.unwrap_or_else(crate::lunes_actual);
+        let mut semana = Self {
+            lunes,
+            fichajes: datos.fichajes.clone(),
+            config: datos.config.clone(),
+            saldo: 0,
+            reglas: datos.reglas.clone(),
+            ausencias: ausencias(datos, lunes),
+            fin_de_semana: fin_de_semana(datos, lunes),
+        };
+        semana.saldo = semana.resumen().saldo();
+        semana
+    }
+
+    pub fn resumen(&self) -> Resumen {
+        calculo::resumen(
+            &self.fichajes,
+            &self.config,
+            &self.ausencias,
+            self.fin_de_semana,
+            &self.reglas,
+        )
+    }
+
+    /// Tiempo fichado de un día, sin completar los días vacíos.
+    pub fn trabajado(&self, dia: usize) -> i64 {
+        calculo::fichado(&self.fichajes, dia, &self.reglas)
+    }
+
+    /// Nombre de la ausencia del día, o "Sin fichajes" si no tiene ninguno.
+    pub fn ausencia(&self, dia: usize) -> &'static str {
+        match self.ausencias[dia] {
+            Some(ausencia) => ausencia.nombre(),
+            None if calculo::dia_vacio(&self.fichajes, dia) => "Sin fichajes",
+            None => "",
+        }
+    }
+
+    /// Fecha de un día de la semana (0 = Lunes).
+    pub fn fecha(&self, dia: usize) -> NaiveDate {
+        self.lunes + Duration::days(dia as i64)
+    }
+
+    /// Extraordinarias de Lunes a Viernes de los días entre `desde` y `hasta`, como si el resto
+    /// de la semana no tuviese fichajes.
+    pub fn extras_entre(&self, desde: NaiveDate, hasta: NaiveDate) -> i64 {
+        let mut fichajes = self.fichajes.clone();
+        for dia in (0..5).filter(|&dia| self.fecha(dia) < desde || hasta < self.fecha(dia)) {
+            for fila in 0..4 {
+                fichajes[dia + fila * 5].cell.clear();
+            }
+        }
+        calculo::extras(&fichajes, &self.ausencias, &self.reglas)
+            .iter()
+            .sum()
+    }
+
+    /// Saldo de los días entre `desde` y `hasta` con las cuentas de la aplicación: un día sin
+    /// fichajes ni ausencia cuenta la jornada. Con los cinco días dentro se restan además el
+    /// Tiempo a Recuperar y las extraordinarias, y el saldo es el de `resumen`.
+    pub fn parcial(&self, desde: NaiveDate, hasta: NaiveDate) -> Parcial {
+        let resumen = self.resumen();
+        let dentro: Vec<usize> = (0..5)
+            .filter(|&dia| desde <= self.fecha(dia) && self.fecha(dia) <= hasta)
+            .collect();
+
+        let mut parcial = Parcial {
+            saldo: dentro
+                .iter()
+                .map(|&dia| resumen.dias[dia] - resumen.objetivos[dia])
+                .sum(),
+            ..Parcial::default()
+        };
+        if dentro.len() == 5 {
+            parcial.recuperar = resumen.recuperar;
+            // Las del fin de semana no están en los días.
+            parcial.extras = resumen.extra() - resumen.fin_de_semana;
+            parcial.saldo -= parcial.recuperar + parcial.extras;
+        }
+        parcial
+    }
+}
+
+/// Parte del saldo de una semana que cae en un rango, para los informes.
+#[derive(Debug, Default, PartialEq)]
+pub struct Parcial {
+    pub saldo: i64,
+    /// Tiempo a Recuperar descontado; sólo con la semana entera.
+    pub recuperar: i64,
+    /// Extraordinarias de Lunes a Viernes descontadas; sólo con la semana entera.
+    pub extras: i64,
+}
+
+/// Ausencias de Lunes a Viernes de la semana que empieza en `lunes`.
+pub fn ausencias(datos: &Datos, lunes: NaiveDate) -> [Option<Ausencia>; 5] {
+    let mut ausencias = [None; 5];
+    for (dia, ausencia) in ausencias.iter_mut().enumerate() {
+        *ausencia = datos
+            .ausencias
+            .get(&(lunes + Duration::days(dia as i64)))
+            .copied();
+    }
+    ausencias
+}
+
+/// Segundos trabajados el sábado y el domingo de la semana de `lunes`.
+pub fn fin_de_semana(datos: &Datos, lunes: NaiveDate) -> i64 {
+    (5..7)
+        .filter_map(|dia| datos.fin_de_semana.get(&(lunes + Duration::days(dia))))
+        .map(|&minutos| i64::from(minutos) * 60)
+        .sum()
+}
+
+/// Guarda la semana actual en el historial. Si ya hay una semana con el mismo lunes no se pierde:
+/// lo fichado en la rejilla manda y las casillas vacías conservan lo archivado.
+pub fn archivar(datos: &mut Datos) {
+    let mut semana = Semana::actual(datos);
+
+    if let Some(n) = datos.historial.iter().position(|s| s.lunes == semana.lunes) {
+        let archivada = datos.historial.remove(n);
+        for (celda, previa) in semana.fichajes.iter_mut().zip(archivada.fichajes) {
+            if celda.cell.trim().is_empty() {
+                *celda = previa;
+            }
+        }
+        semana.saldo = semana.resumen().saldo();
+    }
+    datos.historial.push(semana);
+    datos.historial.sort_by_key(|s| s.lunes);
+}
+
+/// Guarda la semana de la rejilla en el historial y pasa la rejilla a la siguiente, o a la de
+/// `lunes` (la de hoy) si la rejilla se quedó atrás: al cerrar antes de acabar la semana, lo que
+/// se fiche después ya es de la siguiente. Si el historial ya tiene fichajes de la nueva semana,
+/// importados antes de llegar a ella, pasan a la rejilla: de otro modo quedarían ocultos.
+pub fn cerrar(datos: &mut Datos, lunes: NaiveDate) {
+    let cerrada = datos.lunes.unwrap_or(lunes);
+    archivar(datos);
+
+    let lunes = lunes.max(cerrada + Duration::days(7));
+    datos.lunes = Some(lunes);
+    datos.fichajes = match datos.historial.iter().position(|s| s.lunes == lunes) {
+        Some(n) => datos.historial.remove(n).fichajes,
+        None => crate::fichajes_vacios(),
+    };
+}
+
+/// Todas las semanas conocidas, las cerradas y la actual, ordenadas por fecha.
+pub fn semanas(datos: &Datos) -> Vec<Semana> {
+    let actual = Semana::actual(datos);
+
+    let mut semanas: Vec<Semana> = datos
+        .historial
+        .iter()
+        .filter(|s| s.lunes != actual.lunes)
+        .map(|s| {
+            let mut semana = s.clone();
+            semana.ausencias = ausencias(datos, s.lunes);
+            semana.fin_de_semana = fin_de_semana(datos, s.lunes);
+            semana.saldo = semana.resumen().saldo();
+            semana
+        })
+        .collect();
+    semanas.push(actual);
+    semanas.sort_by_key(|s| s.lunes);
+    semanas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fecha(mes: u32, dia: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, mes, dia).unwrap()
+    }
+
+    fn fichar(datos: &mut Datos, celda: usize, hora: &str) {
+        datos.fichajes[celda].cell = format!("      {hora}");
+    }
+
+    fn archivada(datos: &Datos, lunes: NaiveDate) -> Vec<String> {
+        let semana = datos.historial.iter().find(|s| s.lunes == lunes).unwrap();
+        semana
+            .fichajes
+            .iter()
+            .map(|c| c.cell.trim().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn cerrar_dos_veces_en_la_misma_semana() {
+        let mut datos = Datos {
+            lunes: Some(fecha(10, 12)),
+            ..Datos::default()
+        };
+        fichar(&mut datos, 0, "0800");
+        fichar(&mut datos, 5, "1500");
+
+        // El viernes 16: la rejilla pasa a la semana siguiente.
+        cerrar(&mut datos, fecha(10, 12));
+        assert_eq!(datos.lunes, Some(fecha(10, 19)));
+        assert!(datos.fichajes.iter().all(|c| c.cell.is_empty()));
+
+        // Otra vez el mismo viernes: la semana cerrada sigue en el historial.
+        fichar(&mut datos, 1, "0900");
+        cerrar(&mut datos, fecha(10, 12));
+        assert_eq!(datos.lunes, Some(fecha(10, 26)));
+        assert_eq!(datos.historial.len(), 2);
+        assert_eq!(archivada(&datos, fecha(10, 12))[0], "0800");
+        assert_eq!(archivada(&datos, fecha(10, 12))[1], "");
+        assert_eq!(archivada(&datos, fecha(10, 19))[1], "0900");
+
+        // Semanas después: la rejilla pasa a la de hoy.
+        fichar(&mut datos, 2, "1000");
+        cerrar(&mut datos, fecha(11, 9));
+        assert_eq!(datos.lunes, Some(fecha(11, 9)));
+        assert_eq!(datos.historial.len(), 3);
+        assert_eq!(archivada(&datos, fecha(10, 26))[2], "1000");
+    }
+
+    #[test]
+    fn cerrar_trae_lo_importado_de_la_semana_nueva() {
+        let mut datos = Datos {
+            lunes: Some(fecha(10, 12)),
+            ..Datos::default()
+        };
+        let mut importada = Semana::actual(&datos);
+        importada.lunes = fecha(10, 19);
+        importada.fichajes[3].cell = "      0830".to_owned();
+        datos.historial.push(importada);
+
+        cerrar(&mut datos, fecha(10, 19));
+        assert_eq!(datos.lunes, Some(fecha(10, 19)));
+        assert_eq!(datos.fichajes[3].cell.trim(), "0830");
+        assert!(datos.historial.iter().all(|s| s.lunes != fecha(10, 19)));
+    }
+
+    #[test]
+    fn archivar_combina_con_la_semana_archivada() {
+        let mut datos = Datos {
+            lunes: Some(fecha(10, 12)),
+            ..Datos::default()
+        };
+        fichar(&mut datos, 0, "0800");
+        fichar(&mut datos, 1, "0815");
+        archivar(&mut datos);
+
+        datos.fichajes = crate::fichajes_vacios();
+        fichar(&mut datos, 1, "0805");
+        fichar(&mut datos, 2, "0830");
+        archivar(&mut datos);
+
+        assert_eq!(datos.historial.len(), 1);
+        assert_eq!(
+            &archivada(&datos, fecha(10, 12))[..3],
+            ["0800", "0805", "0830"]
+        );
+    }
+}
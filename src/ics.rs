@@ -0,0 +1,279 @@
+use std::fmt::Write as _;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::calculo::{self, Ausencia};
+use crate::historial;
+use crate::Datos;
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Exportar iCalendar                                                                  +++
+// +----------------------------------------------------------------------------------------------+
+
+// Un VEVENT por tramo entrada/salida, con hora local flotante. El UID depende sólo del día y del
+// tramo, así que al volver a exportar se actualizan los eventos en lugar de duplicarlos.
+
+const TRAMOS: [(&str, &str); 2] = [("manana", "Ma\u{f1}ana"), ("tarde", "Tarde")];
+
+fn uid(fecha: NaiveDate, tramo: &str) -> String {
+    format!("{}-{}@horario", fecha.format("%Y%m%d"), tramo)
+}
+
+fn evento(ics: &mut String, uid: &str, inicio: &str, fin: &str, resumen: &str) {
+    let _ = write!(
+        ics,
+        "BEGIN:VEVENT\r\nUID:{}\r\nDTSTAMP:{}\r\n{}\r\n{}\r\nSUMMARY:{}\r\nEND:VEVENT\r\n",
+        uid,
+        Utc::now().format("%Y%m%dT%H%M%SZ"),
+        inicio,
+        fin,
+        resumen
+    );
+}
+
+pub fn generar(datos: &Datos, desde: NaiveDate, hasta: NaiveDate, ausencias: bool) -> String {
+    let mut ics = String::from("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Horario//ES\r\n");
+    let zero = NaiveTime::from_hms_opt(0, 0, 0).unwrap_or_default();
+
+    for semana in historial::semanas(datos) {
+        let fichajes = &semana.fichajes;
+
+        for dia in 0..5 {
+            let fecha = semana.fecha(dia);
+            if fecha < desde || fecha > hasta {
+                continue;
+            }
+
+            let ausencia = semana.ausencia(dia);
+            if ausencias && !ausencia.is_empty() {
+                evento(
+                    &mut ics,
+                    &uid(fecha, "ausencia"),
+                    &format!("DTSTART;VALUE=DATE:{}", fecha.format("%Y%m%d")),
+                    &format!(
+                        "DTEND;VALUE=DATE:{}",
+                        (fecha + Duration::days(1)).format("%Y%m%d")
+                    ),
+                    ausencia,
+                );
+            }
+
+            for (fila, (id, tramo)) in TRAMOS.iter().enumerate() {
+                let entrada = &fichajes[dia + fila * 10].cell;
+                let salida = &fichajes[dia + fila * 10 + 5].cell;
+                if calculo::tramo(entrada, salida) == 0 {
+                    continue;
+                }
+
+                let hora = |cell: &str| {
+                    (fecha.and_time(zero) + Duration::seconds(calculo::segundos(cell)))
+                        .format("%Y%m%dT%H%M%S")
+                };
+                evento(
+                    &mut ics,
+                    &uid(fecha, id),
+                    &format!("DTSTART:{}", hora(entrada)),
+                    &format!("DTEND:{}", hora(salida)),
+                    &format!("Trabajo ({tramo})"),
+                );
+            }
+        }
+    }
+
+    ics.push_str("END:VCALENDAR\r\n");
+    ics
+}
+
+/// Escribe el calendario del rango en `dir` y devuelve la ruta del fichero.
+pub fn exportar(
+    datos: &Datos,
+    desde: NaiveDate,
+    hasta: NaiveDate,
+    ausencias: bool,
+    dir: &Path,
+) -> io::Result<PathBuf> {
+    let ruta = dir.join(format!(
+        "horario-{}-{}.ics",
+        desde.format("%Y%m%d"),
+        hasta.format("%Y%m%d")
+    ));
+    std::fs::write(&ruta, generar(datos, desde, hasta, ausencias))?;
+
+    Ok(ruta)
+}
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Importar Ausencias                                                                  +++
+// +----------------------------------------------------------------------------------------------+
+
+// Cada VEVENT se clasifica por la primera palabra clave que aparezca en CATEGORIES, SUMMARY o el
+// nombre del calendario (X-WR-CALNAME), en ese orden; si no aparece ninguna se usa el tipo por
+// defecto, pensado para ficheros que son todos del mismo tipo (el calendario de festivos).
+
+/// Palabras clave de cada tipo de ausencia, separadas por comas.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Calendario {
+    /// En el orden de `Ausencia::TODAS`.
+    pub palabras: [String; 4],
+    pub por_defecto: Option<Ausencia>,
+}
+
+impl Default for Calendario {
+    fn default() -> Self {
+        Self {
+            palabras: [
+                "festivo, fiesta, holiday".to_owned(),
+                "vacaciones, vacation".to_owned(),
+                "baja".to_owned(),
+                "permiso, asuntos propios".to_owned(),
+            ],
+            por_defecto: None,
+        }
+    }
+}
+
+impl Calendario {
+    fn clasificar(&self, textos: &[&str]) -> Option<Ausencia> {
+        for texto in textos {
+            let texto = texto.to_lowercase();
+            for (palabras, ausencia) in self.palabras.iter().zip(Ausencia::TODAS) {
+                if palabras
+                    .split(',')
+                    .map(|p| p.trim().to_lowercase())
+                    .any(|p| !p.is_empty() && texto.contains(&p))
+                {
+                    return Some(ausencia);
+                }
+            }
+        }
+        self.por_defecto
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Evento {
+    inicio: Option<NaiveDate>,
+    /// Último día incluido.
+    fin: Option<NaiveDate>,
+    resumen: String,
+    categorias: String,
+}
+
+/// Una ausencia leída del calendario, pendiente de que el usuario la acepte.
+#[derive(Clone, Debug)]
+pub struct Propuesta {
+    pub fecha: NaiveDate,
+    pub ausencia: Ausencia,
+    pub anterior: Option<Ausencia>,
+    pub resumen: String,
+    pub aceptar: bool,
+}
+
+/// Fecha de un DTSTART/DTEND, y si es fin de evento exclusivo (día completo o medianoche).
+fn fecha(valor: &str) -> Option<(NaiveDate, bool)> {
+    let fecha = NaiveDate::parse_from_str(valor.get(..8)?, "%Y%m%d").ok()?;
+    let medianoche = valor.len() == 8 || valor.get(8..15) == Some("T000000");
+    Some((fecha, medianoche))
+}
+
+/// Deshace el plegado de líneas de RFC 5545 y quita los escapes de texto.
+fn lineas(texto: &str) -> Vec<String> {
+    let mut lineas: Vec<String> = Vec::new();
+    for linea in texto.lines() {
+        match (linea.strip_prefix([' ', '\t']), lineas.last_mut()) {
+            (Some(resto), Some(ultima)) => ultima.push_str(resto),
+            _ => lineas.push(linea.to_owned()),
+        }
+    }
+    lineas
+}
+
+fn texto(valor: &str) -> String {
+    valor
+        .replace("\\n", " ")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
+
+pub fn propuestas(datos: &Datos, ics: &str, calendario: &Calendario) -> Vec<Propuesta> {
+    let mut nombre = String::new();
+    let mut eventos = Vec::new();
+    let mut evento: Option<Evento> = None;
+
+    for linea in lineas(ics) {
+        let Some((clave, valor)) = linea.split_once(':') else {
+            continue;
+        };
+        let nombre_clave = clave.split(';').next().unwrap_or_default();
+
+        match (nombre_clave, evento.as_mut()) {
+            ("BEGIN", None) if valor == "VEVENT" => evento = Some(Evento::default()),
+            ("END", Some(_)) if valor == "VEVENT" => eventos.extend(evento.take()),
+            ("X-WR-CALNAME", None) => nombre = texto(valor),
+            ("DTSTART", Some(e)) => e.inicio = fecha(valor).map(|(f, _)| f),
+            ("DTEND", Some(e)) => {
+                e.fin = fecha(valor).map(
+                    |(f, medianoche)| {
+                        if medianoche {
+                            f - Duration::days(1)
+                        } else {
+                            f
+                        }
+                    },
+                );
+            }
+            ("SUMMARY", Some(e)) => e.resumen = texto(valor),
+            ("CATEGORIES", Some(e)) => e.categorias = texto(valor),
+            _ => {}
+        }
+    }
+
+    let mut propuestas = Vec::new();
+
+    for evento in eventos {
+        let Some(inicio) = evento.inicio else {
+            continue;
+        };
+        let fin = evento.fin.unwrap_or(inicio).max(inicio);
+        let Some(ausencia) = calendario.clasificar(&[&evento.categorias, &evento.resumen, &nombre])
+        else {
+            continue;
+        };
+
+        let mut fecha = inicio;
+        while fecha <= fin && fecha - inicio < Duration::days(366) {
+            let anterior = datos.ausencias.get(&fecha).copied();
+            if fecha.weekday().num_days_from_monday() < 5
+                && anterior != Some(ausencia)
+                && !propuestas.iter().any(|p: &Propuesta| p.fecha == fecha)
+            {
+                propuestas.push(Propuesta {
+                    fecha,
+                    ausencia,
+                    anterior,
+                    resumen: evento.resumen.clone(),
+                    aceptar: anterior.is_none(),
+                });
+            }
+            fecha += Duration::days(1);
+        }
+    }
+
+    propuestas.sort_by_key(|p| p.fecha);
+    propuestas
+}
+
+/// Guarda las ausencias aceptadas y devuelve cuántas se han aplicado.
+pub fn aplicar(datos: &mut Datos, propuestas: &[Propuesta]) -> usize {
+    let mut n = 0;
+    for propuesta in propuestas.iter().filter(|p| p.aceptar) {
+        datos.ausencias.insert(propuesta.fecha, propuesta.ausencia);
+        n += 1;
+    }
+    n
+}
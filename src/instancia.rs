@@ -0,0 +1,183 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use serde::{Deserialize, Serialize};
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Instancia Única                                                                     +++
+// +----------------------------------------------------------------------------------------------+
+
+// La ventana abierta tiene bloqueado `horario.lock` en la carpeta de datos; el bloqueo lo suelta
+// el sistema al terminar el proceso, aunque sea de golpe. Mientras tanto escucha en el socket
+// `horario.sock` las órdenes de otras invocaciones (una línea JSON con los argumentos) y las
+// ejecuta sobre sus propios datos, así que nadie pisa lo que guarda la ventana. `horario api` sin
+// ventana hace lo mismo.
+
+const CERROJO: &str = "horario.lock";
+#[cfg(unix)]
+const SOCKET: &str = "horario.sock";
+
+/// Cuánto espera el socket a que la ventana atienda una orden.
+#[cfg(unix)]
+const ESPERA: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Cuánto espera el socket a que llegue la orden, para que un cliente colgado no lo bloquee.
+#[cfg(unix)]
+const LECTURA: std::time::Duration = std::time::Duration::from_secs(5);
+
+fn ruta(nombre: &str) -> io::Result<PathBuf> {
+    let dir =
+        crate::data_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "data_dir"))?;
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir.join(nombre))
+}
+
+/// Bloquea el cerrojo, o `None` si ya lo tiene otra instancia.
+pub fn cerrojo() -> io::Result<Option<File>> {
+    let mut fichero = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(ruta(CERROJO)?)?;
+
+    match fichero.try_lock() {
+        Ok(()) => {
+            fichero.set_len(0)?;
+            writeln!(fichero, "{}", std::process::id())?;
+            Ok(Some(fichero))
+        }
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(err)) => Err(err),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Respuesta {
+    ok: bool,
+    texto: String,
+}
+
+/// Una orden recibida por el socket, pendiente de que la ventana la ejecute.
+pub struct Peticion {
+    pub args: Vec<String>,
+    respuesta: Sender<Result<String, String>>,
+}
+
+impl Peticion {
+    pub fn responder(self, resultado: Result<String, String>) {
+        let _ = self.respuesta.send(resultado);
+    }
+}
+
+pub struct Instancia {
+    _cerrojo: File,
+    peticiones: Receiver<Peticion>,
+}
+
+impl std::fmt::Debug for Instancia {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Instancia").finish_non_exhaustive()
+    }
+}
+
+impl Instancia {
+    /// Guarda el cerrojo mientras viva la instancia y empieza a escuchar en el socket. Con cada
+    /// orden se llama a `despertar`, para que la ventana la atienda aunque no se esté pintando.
+    pub fn escuchar(cerrojo: File, despertar: impl Fn() + Send + 'static) -> Self {
+        let (enviar, peticiones) = mpsc::channel();
+
+        #[cfg(unix)]
+        if let Ok(ruta) = ruta(SOCKET) {
+            // Con el cerrojo en la mano, un socket que ya exista es de una instancia terminada.
+            let _ = std::fs::remove_file(&ruta);
+            if let Ok(socket) = std::os::unix::net::UnixListener::bind(&ruta) {
+                std::thread::spawn(move || servir(&socket, &enviar, &despertar));
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = (despertar, enviar);
+
+        Self {
+            _cerrojo: cerrojo,
+            peticiones,
+        }
+    }
+
+    pub fn peticiones(&self) -> Vec<Peticion> {
+        self.peticiones.try_iter().collect()
+    }
+
+    /// Espera a la siguiente orden; `None` si el socket ha dejado de escuchar.
+    pub fn esperar(&self) -> Option<Peticion> {
+        self.peticiones.recv().ok()
+    }
+}
+
+#[cfg(unix)]
+fn servir(
+    socket: &std::os::unix::net::UnixListener,
+    enviar: &Sender<Peticion>,
+    despertar: &dyn Fn(),
+) {
+    use std::io::{BufRead, BufReader};
+
+    for conexion in socket.incoming().flatten() {
+        let _ = conexion.set_read_timeout(Some(LECTURA));
+        let mut linea = String::new();
+        if BufReader::new(&conexion).read_line(&mut linea).is_err() {
+            continue;
+        }
+
+        let resultado = match serde_json::from_str::<Vec<String>>(&linea) {
+            Ok(args) => {
+                let (respuesta, recibir) = mpsc::channel();
+                if enviar.send(Peticion { args, respuesta }).is_err() {
+                    return;
+                }
+                despertar();
+                recibir
+                    .recv_timeout(ESPERA)
+                    .unwrap_or_else(|_| Err("La ventana de Horario no responde".to_owned()))
+            }
+            Err(err) => Err(err.to_string()),
+        };
+
+        let respuesta = match resultado {
+            Ok(texto) => Respuesta { ok: true, texto },
+            Err(texto) => Respuesta { ok: false, texto },
+        };
+        if let Ok(json) = serde_json::to_string(&respuesta) {
+            let _ = writeln!(&conexion, "{json}");
+        }
+    }
+}
+
+/// Manda `args` a la ventana abierta y devuelve su respuesta, o `None` si no hay ninguna
+/// escuchando.
+pub fn enviar(args: &[String]) -> Option<Result<String, String>> {
+    #[cfg(unix)]
+    {
+        use std::io::{BufRead, BufReader};
+
+        let mut conexion = std::os::unix::net::UnixStream::connect(ruta(SOCKET).ok()?).ok()?;
+        let _ = conexion.set_read_timeout(Some(ESPERA + std::time::Duration::from_secs(1)));
+        writeln!(conexion, "{}", serde_json::to_string(args).ok()?).ok()?;
+
+        let mut linea = String::new();
+        BufReader::new(&conexion).read_line(&mut linea).ok()?;
+        let respuesta: Respuesta = serde_json::from_str(&linea).ok()?;
+        Some(if respuesta.ok {
+            Ok(respuesta.texto)
+        } else {
+            Err(respuesta.texto)
+        })
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = args;
+        None
+    }
+}
@@ -0,0 +1,246 @@
+use chrono::Datelike;
+
+use crate::calculo;
+use crate::historial::{self, Semana};
+use crate::{Datos, DAYS_WEEK_NAMES};
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Límites Legales                                                                     +++
+// +----------------------------------------------------------------------------------------------+
+
+// Comprobaciones del Estatuto de los Trabajadores sobre los tramos fichados, con los límites de
+// las reglas de cada semana. Las horas extraordinarias son las que cuentan las reglas de Extras,
+// sumadas por semanas desde la primera del año, como en su bolsa.
+//
+// El descanso semanal no se comprueba: del sábado y el domingo sólo se apuntan los minutos, no
+// las horas, y sin ellos no se sabe cuánto se ha descansado seguido.
+
+const DIA: i64 = 86400;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Aviso {
+    /// Desde la última salida del día anterior hasta la primera entrada del día `dia`.
+    DescansoDiario {
+        dia: usize,
+        descanso: i64,
+        minimo: i64,
+    },
+    JornadaMaxima {
+        dia: usize,
+        trabajado: i64,
+        maximo: i64,
+    },
+
+    /// Extraordinarias del ejercicio hasta el final de la semana.
+    ExtrasAnuales {
+        ejercicio: i32,
+        extras: i64,
+        maximo: i64,
+    },
+}
+
+impl Aviso {
+    /// Día de la semana del aviso; `None` si es de la semana o del ejercicio.
+    pub fn dia(&self) -> Option<usize> {
+        match self {
+            Self::DescansoDiario { dia, .. } | Self::JornadaMaxima { dia, .. } => Some(*dia),
+            Self::ExtrasAnuales { .. } => None,
+        }
+    }
+
+    pub fn texto(&self) -> String {
+        use calculo::horas;
+        match self {
+            Self::DescansoDiario {
+                descanso, minimo, ..
+            } => format!(
+                "Descanso entre jornadas de {} (m\u{ed}nimo {})",
+                horas(*descanso),
+                horas(*minimo)
+            ),
+            Self::JornadaMaxima {
+                trabajado, maximo, ..
+            } => format!(
+                "Jornada de {} (m\u{e1}ximo ordinario {})",
+                horas(*trabajado),
+                horas(*maximo)
+            ),
+
+            Self::ExtrasAnuales {
+                ejercicio,
+                extras,
+                maximo,
+            } => format!(
+                "{} de horas extraordinarias en {} (m\u{e1}ximo {})",
+                horas(*extras),
+                ejercicio,
+                horas(*maximo)
+            ),
+        }
+    }
+
+    /// Con el día delante, si lo tiene.
+    pub fn descripcion(&self) -> String {
+        match self.dia() {
+            Some(dia) => format!("{}: {}", DAYS_WEEK_NAMES[dia], self.texto()),
+            None => self.texto(),
+        }
+    }
+}
+
+/// Tiempo de los tramos tal y como se ficharon: los límites legales no ven el redondeo, las
+/// ventanas ni el descuento del descanso de las reglas.
+fn presencia(tramos: &[(i64, i64)]) -> i64 {
+    tramos
+        .iter()
+        .map(|(entrada, salida)| salida - entrada)
+        .sum()
+}
+
+/// Avisos de la semana `n` de `semanas`, que tienen que estar en orden como las da
+/// `historial::semanas`.
+pub fn avisos(semanas: &[Semana], n: usize) -> Vec<Aviso> {
+    let semana = &semanas[n];
+    let limites = semana.reglas.limites;
+    let mut avisos = Vec::new();
+    if !limites.activos {
+        return avisos;
+    }
+
+    let tramos: Vec<Vec<(i64, i64)>> = (0..5)
+        .map(|dia| calculo::tramos(&semana.fichajes, dia))
+        .collect();
+
+    for dia in 0..5 {
+        // Descanso entre jornadas: sólo entre dos días seguidos con fichajes.
+        let minimo = i64::from(limites.descanso_diario) * 60;
+        if dia > 0 {
+            if let (Some(salida), Some(entrada)) = (tramos[dia - 1].last(), tramos[dia].first()) {
+                let descanso = DIA - salida.1 + entrada.0;
+                if descanso < minimo {
+                    avisos.push(Aviso::DescansoDiario {
+                        dia,
+                        descanso,
+                        minimo,
+                    });
+                }
+            }
+        }
+
+        let maximo = i64::from(limites.jornada) * 60;
+        let trabajado = presencia(&tramos[dia]);
+        if trabajado > maximo {
+            avisos.push(Aviso::JornadaMaxima {
+                dia,
+                trabajado,
+                maximo,
+            });
+        }
+    }
+
+    // Extraordinarias del año del lunes, hasta esta semana incluida.
+    let ejercicio = semana.lunes.year();
+    let extras: i64 = semanas[..=n]
+        .iter()
+        .filter(|s| s.lunes.year() == ejercicio)
+        .map(|s| s.resumen().extra())
+        .sum();
+    let maximo = i64::from(limites.extras) * 60;
+    if extras > maximo {
+        avisos.push(Aviso::ExtrasAnuales {
+            ejercicio,
+            extras,
+            maximo,
+        });
+    }
+
+    avisos
+}
+
+/// Avisos de la semana de la rejilla.
+pub fn actuales(datos: &Datos) -> Vec<Aviso> {
+    let semanas = historial::semanas(datos);
+    let lunes = datos.lunes.unwrap_or_else(crate::lunes_actual);
+    match semanas.iter().position(|s| s.lunes == lunes) {
+        Some(n) => avisos(&semanas, n),
+        None => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn fichar(datos: &mut Datos, dia: usize, entrada: &str, salida: &str) {
+        datos.fichajes[dia].cell = format!("      {entrada}");
+        datos.fichajes[dia + 5].cell = format!("      {salida}");
+    }
+
+    #[test]
+    fn avisos_de_descanso_jornada_y_extraordinarias() {
+        let mut datos = Datos {
+            lunes: NaiveDate::from_ymd_opt(2026, 3, 9),
+            ..Datos::default()
+        };
+        datos.reglas.extras.diaria = 8 * 60;
+        datos.reglas.limites.extras = 5 * 60;
+        // Lunes de 14 horas y el martes se entra a las 6: 8 horas de descanso.
+        fichar(&mut datos, 0, "0800", "2200");
+        fichar(&mut datos, 1, "0600", "1400");
+
+        let avisos = actuales(&datos);
+        assert_eq!(
+            avisos,
+            [
+                Aviso::JornadaMaxima {
+                    dia: 0,
+                    trabajado: 14 * 3600,
+                    maximo: 9 * 3600,
+                },
+                Aviso::DescansoDiario {
+                    dia: 1,
+                    descanso: 8 * 3600,
+                    minimo: 12 * 3600,
+                },
+                Aviso::ExtrasAnuales {
+                    ejercicio: 2026,
+                    extras: 6 * 3600,
+                    maximo: 5 * 3600,
+                },
+            ]
+        );
+        assert_eq!(avisos[0].dia(), Some(0));
+        assert_eq!(avisos[2].dia(), None);
+
+        datos.reglas.limites.activos = false;
+        assert!(actuales(&datos).is_empty());
+    }
+
+    #[test]
+    fn las_extraordinarias_se_suman_solo_en_el_ejercicio() {
+        let mut datos = Datos {
+            lunes: NaiveDate::from_ymd_opt(2025, 12, 29),
+            ..Datos::default()
+        };
+        datos.reglas.extras.diaria = 8 * 60;
+        datos.reglas.limites.extras = 3 * 60;
+        datos.reglas.limites.jornada = 10 * 60;
+        fichar(&mut datos, 0, "0800", "1800");
+        fichar(&mut datos, 4, "0800", "1800");
+        historial::cerrar(&mut datos, NaiveDate::from_ymd_opt(2026, 1, 5).unwrap());
+        fichar(&mut datos, 0, "0800", "1800");
+
+        let semanas = historial::semanas(&datos);
+        assert_eq!(semanas.len(), 2);
+        assert_eq!(
+            avisos(&semanas, 0),
+            [Aviso::ExtrasAnuales {
+                ejercicio: 2025,
+                extras: 4 * 3600,
+                maximo: 3 * 3600,
+            }]
+        );
+        assert!(avisos(&semanas, 1).is_empty());
+    }
+}
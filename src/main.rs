@@ -3,23 +3,53 @@
 #![warn(clippy::all, rust_2018_idioms)]
 #![cfg_attr(windows, windows_subsystem = "windows")]
 
-use chrono::{Datelike, Duration, NaiveTime, Utc};
-use directories_next::ProjectDirs;
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveTime, Timelike};
+use directories_next::{ProjectDirs, UserDirs};
+use eframe::egui::text::{LayoutJob, TextFormat};
 use eframe::egui::{
-    vec2, Align, Align2, Button, CentralPanel, Color32, Context, FontId, Frame, Grid, Id, Key,
-    Label, Layout, Rgba, RichText, Sense, TextEdit, Ui, Visuals,
+    vec2, Align, Align2, Button, CentralPanel, Color32, ComboBox, Context, DragValue, FontId,
+    Frame, Grid, Id, Key, Label, Layout, Rgba, RichText, ScrollArea, Sense, TextEdit, Ui, Visuals,
 };
 
 use eframe::{get_value, run_native, set_value, App, NativeOptions, Storage, APP_KEY};
 
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::usize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+mod almacen;
+mod anual;
+mod api;
+mod auditoria;
+mod bandeja;
+mod barra;
+mod bolsa;
+mod calculo;
+mod cli;
+mod copia;
+mod csv;
+mod estado;
+mod firma;
+mod historial;
+mod ics;
+mod instancia;
+mod legal;
+mod pdf;
+mod reglas;
+mod sesion;
+mod webhook;
+
+use auditoria::Motivo;
+use calculo::Ausencia;
+use reglas::Extra;
 
 const APPNAME: &str = "Horario";
 
 const HM: &str = "%H%M";
 
+const DMY: &str = "%d/%m/%Y";
+
 const SHORT_MONTH_NAMES: [&str; 13] = [
     "---", "Ene", "Feb", "Mar", "Abr", "May", "Jun", "Jul", "Ago", "Sep", "Oct", "Nov", "Dic",
 ];
@@ -34,6 +64,13 @@ const DAYS_WEEK_NAMES: [&str; 7] = [
     "Domingo",
 ];
 
+const FICHAJE_NAMES: [&str; 4] = [
+    "Entrada Ma\u{f1}ana",
+    "Salida Ma\u{f1}ana",
+    "Entrada Tarde",
+    "Salida Tarde",
+];
+
 const CONFIG_FIELDS: [&str; 4] = [
     "Saldo Semanal / 5:",
     "Obligatorio Tardes:  [ \u{2605} ]",
@@ -43,16 +80,58 @@ const CONFIG_FIELDS: [&str; 4] = [
 
 const CONFIG_SALDO: [&str; 3] = ["\u{26f6}", "\u{2796}", "\u{2795}"];
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 enum Menu {
+    #[default]
     Horario,
     Configurar,
+    Cabecera,
+    Reglas,
+    Api,
+    Webhook,
+    Cerrar,
+    Auditoria,
+    Exportar,
+    Importar,
+    Ausencias,
+    Sesiones,
+    Bolsa,
+    Extras,
+    Anual,
     About,
 }
 
-impl Default for Menu {
-    fn default() -> Self {
-        Self::Horario
+/// Apartados de la pantalla de reglas.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Apartado {
+    #[default]
+    Redondeo,
+    Descanso,
+    Franja,
+    Ventana,
+    Limites,
+    Extras,
+}
+
+impl Apartado {
+    const TODOS: [Apartado; 6] = [
+        Apartado::Redondeo,
+        Apartado::Descanso,
+        Apartado::Franja,
+        Apartado::Ventana,
+        Apartado::Limites,
+        Apartado::Extras,
+    ];
+
+    fn nombre(self) -> &'static str {
+        match self {
+            Self::Redondeo => "Redondeo",
+            Self::Descanso => "Descanso",
+            Self::Franja => "Presencia",
+            Self::Ventana => "Computable",
+            Self::Limites => "L\u{ed}mites",
+            Self::Extras => "Extras",
+        }
     }
 }
 
@@ -67,17 +146,57 @@ struct Cell {
 struct Datos {
     fichajes: Vec<Cell>,
     config: Vec<Cell>,
+    /// Lunes de la semana a la que pertenecen los fichajes.
+    #[serde(default)]
+    lunes: Option<NaiveDate>,
+    #[serde(default)]
+    historial: Vec<historial::Semana>,
+    /// Columnas y formatos del último CSV de fichadas importado.
+    #[serde(default)]
+    importacion: csv::Importacion,
+    #[serde(default)]
+    cabecera: pdf::Cabecera,
+    /// Festivos, vacaciones y demás días sin jornada.
+    #[serde(default)]
+    ausencias: BTreeMap<NaiveDate, Ausencia>,
+    /// Minutos trabajados cada sábado y domingo, que no tienen casilla en la rejilla.
+    #[serde(default)]
+    fin_de_semana: BTreeMap<NaiveDate, u32>,
+    /// Palabras clave del último calendario de ausencias importado.
+    #[serde(default)]
+    calendario: ics::Calendario,
+    /// Cerrar la ventana a la bandeja del sistema en lugar de salir.
+    #[serde(default)]
+    bandeja: bool,
+    #[serde(default)]
+    api: api::Config,
+    #[serde(default)]
+    webhook: webhook::Config,
+    #[serde(default)]
+    sesion: sesion::Config,
+    /// Redondeos y demás reglas del convenio para contar las horas.
+    #[serde(default)]
+    reglas: reglas::Reglas,
+    /// Tope y caducidad del saldo acumulado.
+    #[serde(default)]
+    bolsa: bolsa::Config,
+    #[serde(default)]
+    anual: anual::Config,
+}
+
+fn fichajes_vacios() -> Vec<Cell> {
+    let iter = (0..20).map(|_a| Cell {
+        is_edit: false,
+        cell: String::new(),
+    });
+
+    Vec::from_iter(iter)
 }
 
 impl Default for Datos {
     fn default() -> Self {
-        let iter = (0..20).map(|_a| Cell {
-            is_edit: false,
-            cell: String::new(),
-        });
-
         Self {
-            fichajes: Vec::from_iter(iter),
+            fichajes: fichajes_vacios(),
             config: vec![
                 Cell {
                     is_edit: false,
@@ -96,31 +215,94 @@ impl Default for Datos {
                     cell: "false".to_owned(),
                 },
             ],
+            lunes: None,
+            historial: Vec::new(),
+            importacion: csv::Importacion::default(),
+            cabecera: pdf::Cabecera::default(),
+            ausencias: BTreeMap::new(),
+            fin_de_semana: BTreeMap::new(),
+            calendario: ics::Calendario::default(),
+            bandeja: false,
+            api: api::Config::default(),
+            webhook: webhook::Config::default(),
+            sesion: sesion::Config::default(),
+            reglas: reglas::Reglas::default(),
+            bolsa: bolsa::Config::default(),
+            anual: anual::Config::default(),
+        }
+    }
+}
+
+/// Lo que la pantalla principal saca de todas las semanas. Se rehace sólo si cambian los datos
+/// de los que sale o el día, no en cada repintado.
+#[derive(Debug, Default)]
+struct Calculos {
+    base: Option<(NaiveDate, Datos)>,
+    avisos: Vec<legal::Aviso>,
+    bolsa: bolsa::Bolsa,
+    anual: anual::Computo,
+}
+
+impl Calculos {
+    fn actualizar(&mut self, datos: &Datos, hoy: NaiveDate) {
+        let vigente = self.base.as_ref().is_some_and(|(dia, base)| {
+            *dia == hoy
+                && base.fichajes == datos.fichajes
+                && base.config == datos.config
+                && base.lunes == datos.lunes
+                && base.historial == datos.historial
+                && base.ausencias == datos.ausencias
+                && base.fin_de_semana == datos.fin_de_semana
+                && base.reglas == datos.reglas
+                && base.bolsa == datos.bolsa
+                && base.anual == datos.anual
+        });
+        if vigente {
+            return;
         }
+
+        self.avisos = legal::actuales(datos);
+        self.bolsa = bolsa::calcular(datos, hoy);
+        self.anual = anual::calcular(datos, hoy);
+        self.base = Some((hoy, datos.clone()));
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Debug, Default)]
 struct Horario {
     datos: Datos,
     x: usize,
     check: bool,
     menu: Menu,
-}
-
-impl Default for Horario {
-    fn default() -> Self {
-        Self {
-            datos: Datos::default(),
-            x: 0,
-            check: false,
-            menu: Menu::default(),
-        }
-    }
+    apartado: Apartado,
+    /// Último valor registrado de cada fichaje en la auditoría.
+    fichados: Vec<String>,
+    registro: Vec<auditoria::Entrada>,
+    mensaje: String,
+    /// Rango de fechas a exportar, `dd/mm/aaaa`.
+    desde: String,
+    hasta: String,
+    /// Añadir al ICS un evento de día completo por cada ausencia.
+    ics_ausencias: bool,
+    /// Fichero a importar y cambios pendientes de revisar.
+    fichero: String,
+    propuestas: Vec<csv::Propuesta>,
+    ausencias: Vec<ics::Propuesta>,
+    bandeja: Option<bandeja::Bandeja>,
+    instancia: Option<instancia::Instancia>,
+    servidor: Option<api::Servidor>,
+    enviador: Option<webhook::Enviador>,
+    /// Resultado del botón Probar del webhook, mientras se envía.
+    prueba: Option<std::sync::mpsc::Receiver<std::io::Result<()>>>,
+    vigilante: webhook::Vigilante,
+    vigia: Option<sesion::Vigia>,
+    /// Sucesos de la sesión en la semana de la rejilla.
+    sucesos: Vec<sesion::Suceso>,
+    calculos: Calculos,
 }
 
 impl Horario {
-    fn new(cc: &eframe::CreationContext<'_>) -> Self {
+    fn new(cc: &eframe::CreationContext<'_>, cerrojo: std::io::Result<std::fs::File>) -> Self {
         cc.egui_ctx.set_visuals(Visuals::dark());
 
         let mut data = Self::default();
@@ -128,6 +310,31 @@ impl Horario {
             data.datos = get_value(storage, APP_KEY).unwrap_or_default();
             data.check = data.datos.config[3].cell == "true";
         }
+        data.datos.lunes.get_or_insert_with(lunes_actual);
+        data.bandeja = bandeja::Bandeja::iniciar(&cc.egui_ctx);
+        let ctx = cc.egui_ctx.clone();
+        match cerrojo {
+            Ok(cerrojo) => {
+                data.instancia = Some(instancia::Instancia::escuchar(cerrojo, move || {
+                    ctx.request_repaint();
+                }));
+            }
+            // Sin cerrojo no hay socket, API ni sesión: que se vea por qué.
+            Err(err) => data.fallo("Sin bloquear horario.lock", err),
+        }
+        data.enviador = Some(webhook::Enviador::iniciar());
+        if data.datos.api.activa && data.instancia.is_some() {
+            data.servidor = api::Servidor::iniciar(&data.datos.api, &cc.egui_ctx).ok();
+        }
+        if data.datos.sesion.activa && data.instancia.is_some() {
+            data.vigia = sesion::Vigia::iniciar().ok();
+        }
+        data.fichados = data
+            .datos
+            .fichajes
+            .iter()
+            .map(|x| x.cell.trim().to_owned())
+            .collect();
         data
     }
 
@@ -163,6 +370,19 @@ impl Horario {
                 match self.menu {
                     Menu::Horario => self.menu_horario(ui),
                     Menu::Configurar => self.menu_configurar(ui),
+                    Menu::Cabecera => self.menu_cabecera(ui),
+                    Menu::Reglas => self.menu_reglas(ui),
+                    Menu::Api => self.menu_api(ui, ctx),
+                    Menu::Webhook => self.menu_webhook(ui),
+                    Menu::Cerrar => self.menu_cerrar(ui),
+                    Menu::Auditoria => self.menu_auditoria(ui),
+                    Menu::Exportar => self.menu_exportar(ui),
+                    Menu::Importar => self.menu_importar(ui),
+                    Menu::Ausencias => self.menu_ausencias(ui),
+                    Menu::Sesiones => self.menu_sesiones(ui),
+                    Menu::Bolsa => self.menu_bolsa(ui),
+                    Menu::Extras => self.menu_extras(ui),
+                    Menu::Anual => self.menu_anual(ui),
                     Menu::About => self.menu_about(ui),
                 };
             });
@@ -220,17 +440,66 @@ impl Horario {
                 // Reset
                 if ui
                     .button(" \u{21ba} ")
-                    .on_hover_text("Eliminar todos los fichajes")
+                    .on_hover_text("Cerrar la semana")
                     .clicked()
                 {
-                    self.menu = Menu::Horario;
-
-                    let iter = (0..20).map(|_a| Cell {
-                        is_edit: false,
-                        cell: String::new(),
-                    });
-
-                    self.datos.fichajes = Vec::from_iter(iter);
+                    self.menu = Menu::Cerrar;
+                }
+                ui.add_space(5.);
+                // Auditoría
+                if ui
+                    .button(" \u{1f4cb} ")
+                    .on_hover_text("Registro de fichajes")
+                    .clicked()
+                {
+                    self.registro = auditoria::leer();
+                    self.mensaje.clear();
+                    self.menu = Menu::Auditoria;
+                }
+                ui.add_space(5.);
+                // Exportar
+                if ui.button(" \u{1f4be} ").on_hover_text("Exportar").clicked() {
+                    if self.desde.is_empty() {
+                        let hoy = Local::now().date_naive();
+                        self.desde = hoy.with_day(1).unwrap_or(hoy).format(DMY).to_string();
+                        self.hasta = hoy.format(DMY).to_string();
+                    }
+                    self.mensaje.clear();
+                    self.menu = Menu::Exportar;
+                }
+                ui.add_space(5.);
+                // Importar
+                if ui
+                    .button(" \u{1f4e5} ")
+                    .on_hover_text("Importar fichadas")
+                    .clicked()
+                {
+                    self.mensaje.clear();
+                    self.menu = Menu::Importar;
+                }
+                ui.add_space(5.);
+                // Ausencias
+                if ui
+                    .button(" \u{1f4c5} ")
+                    .on_hover_text("Importar festivos y ausencias (ICS)")
+                    .clicked()
+                {
+                    self.mensaje.clear();
+                    self.menu = Menu::Ausencias;
+                }
+                ui.add_space(5.);
+                // Sesiones
+                if ui
+                    .button(" \u{1f5a5} ")
+                    .on_hover_text("Fichajes propuestos por la actividad de la sesi\u{f3}n")
+                    .clicked()
+                {
+                    let lunes = self.datos.lunes.unwrap_or_else(lunes_actual);
+                    self.desde = lunes.format(DMY).to_string();
+                    self.hasta = (lunes + Duration::days(4)).format(DMY).to_string();
+                    self.leer_sucesos(false);
+                    self.mensaje.clear();
+                    self.menu = Menu::Sesiones;
                 }
             });
         });
@@ -240,7 +509,8 @@ impl Horario {
                 ui.spacing_mut().item_spacing.x = 0.0;
                 ui.visuals_mut().button_frame = false;
                 ui.add_space(8.0);
-                close_maximize_minimize(ui, frame);
+                let a_bandeja = self.datos.bandeja && self.bandeja.is_some();
+                close_maximize_minimize(ui, frame, a_bandeja);
 
                 ui.add_space(12.0);
                 // About
@@ -266,7 +536,7 @@ impl Horario {
 
             ui.add_space(5.);
 
-            let dt = Utc::now();
+            let dt = Local::now();
 
             // Mon=1, ..., Vie=5, Sat=6, Sun=7
             let num_day_week = dt.weekday().number_from_monday();
@@ -274,18 +544,37 @@ impl Horario {
 
             ui.set_max_width(500.);
 
+            let lunes = self.datos.lunes.unwrap_or_else(lunes_actual);
+            let ausencias = historial::ausencias(&self.datos, lunes);
+            let franjas = historial::Semana::actual(&self.datos).resumen().franjas;
+            self.calculos
+                .actualizar(&self.datos, Local::now().date_naive());
+            let avisos = self.calculos.avisos.clone();
+
             ui.horizontal(|ui| {
                 ui.add_space(23.);
                 for (i, item) in DAYS_WEEK_NAMES.iter().enumerate().take(5) {
-                    let color = if (ndw - 1) == i {
+                    let color = if ausencias[i].is_some() {
+                        Color32::GRAY
+                    } else if (ndw - 1) == i {
                         Color32::KHAKI
                     } else {
                         Color32::LIGHT_BLUE
                     };
+                    // Franja obligatoria sin cubrir y límites legales
+                    let legales: Vec<&legal::Aviso> =
+                        avisos.iter().filter(|a| a.dia() == Some(i)).collect();
+                    let mut nombre = item.to_string();
+                    if franjas[i] > 0 {
+                        nombre.push_str(" \u{26a0}");
+                    }
+                    if !legales.is_empty() {
+                        nombre.push_str(" \u{2696}");
+                    }
                     let label = ui.add_sized(
                         [102., 50.],
                         Label::new(
-                            RichText::new(format!("{}", *item))
+                            RichText::new(nombre)
                                 .color(color)
                                 .font(FontId::proportional(20.)),
                         )
@@ -295,32 +584,89 @@ impl Horario {
                     if label.clicked() {
                         self.x = i;
                     }
+
+                    // Ausencia del día (botón derecho)
+                    let fecha = lunes + Duration::days(i as i64);
+                    let mut notas: Vec<String> = ausencias[i]
+                        .map(|ausencia| ausencia.nombre().to_owned())
+                        .into_iter()
+                        .collect();
+                    if franjas[i] > 0 {
+                        notas.push(format!(
+                            "\u{26a0} {} sin cubrir de la franja {}",
+                            calculo::horas(franjas[i]),
+                            self.datos.reglas.franjas[i].texto()
+                        ));
+                    }
+                    notas.extend(legales.iter().map(|a| format!("\u{2696} {}", a.texto())));
+                    let label = match notas.is_empty() {
+                        true => label,
+                        false => label.on_hover_text(notas.join("\n")),
+                    };
+                    label.context_menu(|ui| {
+                        if ui.button("Sin ausencia").clicked() {
+                            self.datos.ausencias.remove(&fecha);
+                            ui.close_menu();
+                        }
+                        for ausencia in Ausencia::TODAS {
+                            if ui.button(ausencia.nombre()).clicked() {
+                                self.datos.ausencias.insert(fecha, ausencia);
+                                ui.close_menu();
+                            }
+                        }
+                    });
                 }
             });
 
             let mut index_cell: usize = 0;
+            let reglas = self.datos.reglas.clone();
 
-            for row in self.datos.fichajes.chunks_mut(5) {
+            for (fila, row) in self.datos.fichajes.chunks_mut(5).enumerate() {
                 ui.horizontal(|ui| {
                     ui.add_space(18.);
-                    for cell in row.iter_mut() {
+                    for (dia, cell) in row.iter_mut().enumerate() {
                         let t: NaiveTime =
                             NaiveTime::parse_from_str(cell.cell.trim(), HM).unwrap_or(zero);
 
-                        let txt_button = if t != zero {
-                            t.format("%H : %M").to_string()
-                        } else {
-                            "".to_string()
-                        };
+                        // La hora fichada y, en pequeño, la que cuenta si se redondea o queda
+                        // fuera del horario computable (✂).
+                        let mut txt_button = LayoutJob::default();
+                        if t != zero {
+                            let fichada = calculo::segundos(&cell.cell);
+                            let cuenta = if fila % 2 == 0 {
+                                reglas.entrada(dia, fichada)
+                            } else {
+                                reglas.salida(dia, fichada)
+                            };
+                            let recortada = reglas.ventanas[dia].recortar(fichada) != fichada;
+
+                            txt_button.append(
+                                &t.format("%H : %M").to_string(),
+                                0.,
+                                TextFormat::simple(
+                                    FontId::proportional(20.),
+                                    ui.visuals().text_color(),
+                                ),
+                            );
+                            if cuenta != fichada || recortada {
+                                let (marca, color) = match recortada {
+                                    true => ("\u{2702}", Color32::GOLD),
+                                    false => ("", Color32::GRAY),
+                                };
+                                txt_button.append(
+                                    &format!(
+                                        "{marca}{:02}:{:02}",
+                                        cuenta / 3600,
+                                        cuenta % 3600 / 60
+                                    ),
+                                    6.,
+                                    TextFormat::simple(FontId::proportional(12.), color),
+                                );
+                            }
+                        }
 
                         let cool_button = ui
-                            .add_sized(
-                                [110.25, 30.],
-                                Label::new(
-                                    RichText::new(format!("{}", &txt_button))
-                                        .font(FontId::proportional(20.)),
-                                ),
-                            )
+                            .add_sized([110.25, 30.], Label::new(txt_button))
                             .interact(Sense::click());
 
                         ui.allocate_ui_at_rect(cool_button.rect, |ui| {
@@ -356,18 +702,99 @@ impl Horario {
                             }
                         });
 
-                        index_cell = index_cell + 1;
+                        index_cell += 1;
                     }
                 });
             }
 
+            self.auditar(Motivo::Manual);
+
             if !self.datos.fichajes[self.x].is_edit {
-                self.check_fichaje()
+                self.check_fichaje();
+                self.auditar(Motivo::Correccion);
             };
 
             ui.add_space(30.);
 
-            ui.label(self.calculo_saldo());
+            let saldo = ui.add(Label::new(self.calculo_saldo()).sense(Sense::click()));
+            if saldo.clicked() {
+                self.menu = Menu::Bolsa;
+            }
+            if !self.mensaje.is_empty() {
+                let mensaje = RichText::new(&self.mensaje).color(Color32::KHAKI);
+                let mensaje = ui.add(Label::new(mensaje).sense(Sense::click()));
+                if mensaje.on_hover_text("Clic para cerrar").clicked() {
+                    self.mensaje.clear();
+                }
+            }
+            let reglas = &self.datos.reglas.descanso;
+            let resumen = historial::Semana::actual(&self.datos).resumen();
+            let mut notas = Vec::new();
+            let descanso = resumen.descansos[self.x % 5];
+            if descanso > 0 {
+                notas.push(format!(
+                    "\u{2615} Descontados {} por no parar {} min tras {} de trabajo",
+                    calculo::horas(descanso),
+                    reglas.minimo,
+                    calculo::horas(i64::from(reglas.tras) * 60)
+                ));
+            }
+            // Resumen de la semana: días que no cubren la franja obligatoria
+            for dia in (0..5).filter(|&dia| resumen.franjas[dia] > 0) {
+                notas.push(format!(
+                    "\u{26a0} {}: {} sin cubrir de {}",
+                    DAYS_WEEK_NAMES[dia],
+                    calculo::horas(resumen.franjas[dia]),
+                    self.datos.reglas.franjas[dia].texto()
+                ));
+            }
+            notas.extend(
+                self.calculos
+                    .avisos
+                    .iter()
+                    .map(|a| format!("\u{2696} {}", a.descripcion())),
+            );
+            if resumen.extra() > 0 {
+                let extras: Vec<String> = Extra::TODAS
+                    .iter()
+                    .filter(|&&extra| resumen.extras[extra as usize] > 0)
+                    .map(|&extra| {
+                        format!(
+                            "{} {}",
+                            extra.nombre(),
+                            calculo::horas(resumen.extras[extra as usize])
+                        )
+                    })
+                    .collect();
+                notas.push(format!(
+                    "\u{23f1} Extraordinarias, fuera del saldo: {}",
+                    extras.join(", ")
+                ));
+            }
+            let bolsa = &self.calculos.bolsa;
+            let mut linea = format!(
+                "\u{1f3e6} Bolsa de horas: {}",
+                estado::saldo_texto(bolsa.saldo())
+            );
+            if let Some(lote) = bolsa.en_riesgo() {
+                linea += &format!(
+                    "; {} caducan el {}",
+                    calculo::horas(lote.segundos),
+                    lote.caduca.unwrap_or_default().format(DMY)
+                );
+            }
+            notas.push(linea + " (clic para verla)");
+            if self.datos.anual.horas > 0 {
+                let computo = &self.calculos.anual;
+                notas.push(format!(
+                    "\u{1f4c6} C\u{f3}mputo anual: {}; a fin de a\u{f1}o {}",
+                    estado::saldo_texto(computo.saldo()),
+                    estado::saldo_texto(computo.previsto)
+                ));
+            }
+            if !notas.is_empty() {
+                saldo.on_hover_text(notas.join("\n"));
+            }
         });
     }
 
@@ -388,9 +815,7 @@ impl Horario {
                 .min_col_width(110.)
                 .spacing([15., 20.])
                 .show(ui, |ui| {
-                    let mut index_cell: usize = 0;
-
-                    for cell in self.datos.config.iter_mut() {
+                    for (index_cell, cell) in self.datos.config.iter_mut().enumerate() {
                         ui.label("");
                         ui.add(Label::new(
                             RichText::new(CONFIG_FIELDS[index_cell])
@@ -398,7 +823,9 @@ impl Horario {
                         ));
 
                         if index_cell == 3 {
-                            ui.checkbox(&mut self.check, "");
+                            if ui.checkbox(&mut self.check, "").changed() {
+                                cell.cell = self.check.to_string();
+                            }
                         } else {
                             let t: NaiveTime =
                                 NaiveTime::parse_from_str(cell.cell.trim(), HM).unwrap_or(zero);
@@ -413,8 +840,7 @@ impl Horario {
                                 .add_sized(
                                     [116.5, 30.],
                                     Label::new(
-                                        RichText::new(format!("{}", &txt_button))
-                                            .font(FontId::proportional(20.)),
+                                        RichText::new(&txt_button).font(FontId::proportional(20.)),
                                     ),
                                 )
                                 .interact(Sense::click());
@@ -427,11 +853,9 @@ impl Horario {
                                         .cursor_at_end(true),
                                 );
 
-                                if cool_button.clicked() {
-                                    if !cell.is_edit {
-                                        cell.is_edit = true;
-                                        text.request_focus();
-                                    }
+                                if cool_button.clicked() && !cell.is_edit {
+                                    cell.is_edit = true;
+                                    text.request_focus();
                                 }
 
                                 if ui.input(|i| i.key_pressed(Key::Enter))
@@ -453,344 +877,2072 @@ impl Horario {
                         }
 
                         ui.end_row();
-                        index_cell = index_cell + 1;
                     }
                 });
 
             ui.add_space(30.);
 
-            let button = Button::new(RichText::new("Aceptar").font(FontId::proportional(17.)));
-            if ui.add_sized([100., 25.], button).clicked() {
-                self.menu = Menu::Horario;
-            };
+            ui.horizontal(|ui| {
+                ui.add_space(if self.bandeja.is_some() { 15. } else { 50. });
+                let button = Button::new(RichText::new("Aceptar").font(FontId::proportional(17.)));
+                if ui.add_sized([100., 25.], button).clicked() {
+                    self.menu = Menu::Horario;
+                };
+
+                ui.add_space(20.);
+
+                let button = Button::new(RichText::new("Reglas").font(FontId::proportional(17.)));
+                if ui
+                    .add_sized([100., 25.], button)
+                    .on_hover_text("Redondeos y dem\u{e1}s reglas del convenio")
+                    .clicked()
+                {
+                    self.menu = Menu::Reglas;
+                };
+
+                ui.add_space(20.);
+
+                let button = Button::new(RichText::new("Informe").font(FontId::proportional(17.)));
+                if ui
+                    .add_sized([100., 25.], button)
+                    .on_hover_text("Empresa y trabajador del informe mensual")
+                    .clicked()
+                {
+                    self.menu = Menu::Cabecera;
+                };
+
+                ui.add_space(20.);
+
+                let button = Button::new(RichText::new("API").font(FontId::proportional(17.)));
+                if ui
+                    .add_sized([100., 25.], button)
+                    .on_hover_text("API HTTP local para otras aplicaciones")
+                    .clicked()
+                {
+                    self.mensaje.clear();
+                    self.menu = Menu::Api;
+                };
+
+                if self.bandeja.is_some() {
+                    ui.add_space(20.);
+                    ui.checkbox(&mut self.datos.bandeja, "Bandeja")
+                        .on_hover_text(if bandeja::wayland() {
+                            "Minimizar la ventana en lugar de cerrarla"
+                        } else {
+                            "Cerrar la ventana a la bandeja del sistema"
+                        });
+                }
+            });
         });
     }
 
-    fn menu_about(&mut self, ui: &mut Ui) {
+    fn menu_cabecera(&mut self, ui: &mut Ui) {
         ui.vertical_centered(|ui| {
-            ui.add_space(50.);
-
+            ui.add_space(15.);
             ui.label(
-                RichText::new(" \u{00a9} 2022 Trapalleiro")
-                    .font(FontId::proportional(24.))
-                    .color(Color32::LIGHT_BLUE),
+                RichText::new("Informe Mensual")
+                    .color(Color32::DEBUG_COLOR)
+                    .font(FontId::proportional(24.)),
             );
+            ui.add_space(20.);
 
-            // Called on shutdown, and perhaps at regular intervals.
-            // Allows you to save state.
-
-            // Only called when the “persistence” feature is enabled.
-
-            // On web the state is stored to “Local Storage”.
-            // On native the path is picked using directories_next::ProjectDirs::data_dir which is:
+            let cab = &mut self.datos.cabecera;
+            Grid::new("cabecera")
+                .num_columns(3)
+                .min_col_width(110.)
+                .spacing([15., 12.])
+                .show(ui, |ui| {
+                    for (nombre, campo) in [
+                        ("Empresa:", &mut cab.empresa),
+                        ("CIF:", &mut cab.cif),
+                        ("Centro:", &mut cab.centro),
+                        ("Trabajador:", &mut cab.trabajador),
+                        ("NIF:", &mut cab.nif),
+                    ] {
+                        ui.label("");
+                        ui.label(RichText::new(nombre).font(FontId::proportional(20.)));
+                        ui.add_sized([260., 24.], TextEdit::singleline(campo));
+                        ui.end_row();
+                    }
+                });
 
-            //     Linux: /home/UserName/.local/share/APPNAME
-            //         macOS: /Users/UserName/Library/Application Support/APPNAME
-            //             Windows: C:\Users\UserName\AppData\Roaming\APPNAME
+            ui.add_space(25.);
 
-            //             where APPNAME is what is given to eframe::run_native.
+            let button = Button::new(RichText::new("Aceptar").font(FontId::proportional(17.)));
+            if ui.add_sized([100., 25.], button).clicked() {
+                self.menu = Menu::Configurar;
+            };
+        });
+    }
 
+    fn menu_reglas(&mut self, ui: &mut Ui) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(10.);
+            ui.horizontal(|ui| {
+                ui.add_space(10.);
+                for apartado in Apartado::TODOS {
+                    ui.selectable_value(
+                        &mut self.apartado,
+                        apartado,
+                        RichText::new(apartado.nombre()).font(FontId::proportional(20.)),
+                    );
+                    ui.add_space(4.);
+                }
+            });
             ui.add_space(20.);
 
-            if let Some(proj_dirs) = ProjectDirs::from("", "", APPNAME) {
-                ui.label(
-                    RichText::new("Datos\n")
-                        .font(FontId::proportional(24.))
-                        .color(Color32::GOLD),
-                );
-                ui.label(
-                    RichText::new(proj_dirs.data_dir().to_string_lossy())
-                        .font(FontId::proportional(20.))
-                        .color(Color32::DEBUG_COLOR),
-                );
+            match self.apartado {
+                Apartado::Redondeo => self.reglas_redondeo(ui),
+                Apartado::Descanso => self.reglas_descanso(ui),
+                Apartado::Franja => {
+                    let ayuda = "Horas de presencia obligatoria; igual a Desde = sin franja";
+                    rejilla_franjas(ui, "franja", &mut self.datos.reglas.franjas, ayuda);
+                }
+                Apartado::Ventana => {
+                    let ayuda = "Lo fichado fuera de estas horas no cuenta; igual a Desde = todo";
+                    rejilla_franjas(ui, "ventana", &mut self.datos.reglas.ventanas, ayuda);
+                }
+                Apartado::Limites => self.reglas_limites(ui),
+                Apartado::Extras => self.reglas_extras(ui),
             }
 
-            ui.add_space(50.);
+            ui.add_space(20.);
 
             let button = Button::new(RichText::new("Aceptar").font(FontId::proportional(17.)));
             if ui.add_sized([100., 25.], button).clicked() {
-                self.menu = Menu::Horario;
+                self.menu = Menu::Configurar;
             };
         });
     }
 
-    // --------------------------------------------------------------------------------------------
-
-    fn check_fichaje(&mut self) {
-        let from_str = NaiveTime::parse_from_str;
-        let since = NaiveTime::signed_duration_since;
-        let zero = from_str("0000", HM).unwrap();
-        let i = self.x;
+    fn reglas_redondeo(&mut self, ui: &mut Ui) {
+        let reglas = &mut self.datos.reglas;
+        Grid::new("redondeo")
+            .num_columns(4)
+            .min_col_width(90.)
+            .spacing([15., 12.])
+            .show(ui, |ui| {
+                ui.label("");
+                ui.label(RichText::new("Cada").color(Color32::GRAY));
+                ui.label(RichText::new("Hacia").color(Color32::GRAY));
+                ui.label(RichText::new("Margen").color(Color32::GRAY));
+                ui.end_row();
+
+                for (nombre, redondeo) in [
+                    ("Entradas:", &mut reglas.entradas),
+                    ("Salidas:", &mut reglas.salidas),
+                    ("Tramos:", &mut reglas.tramos),
+                    ("D\u{ed}a:", &mut reglas.dias),
+                ] {
+                    ui.label(RichText::new(nombre).font(FontId::proportional(20.)));
+                    ui.add(
+                        DragValue::new(&mut redondeo.minutos)
+                            .clamp_range(0..=60)
+                            .suffix(" min"),
+                    )
+                    .on_hover_text("0 = sin redondeo");
+                    ComboBox::from_id_source(nombre)
+                        .selected_text(redondeo.direccion.nombre())
+                        .show_ui(ui, |ui| {
+                            for direccion in reglas::Direccion::TODAS {
+                                ui.selectable_value(
+                                    &mut redondeo.direccion,
+                                    direccion,
+                                    direccion.nombre(),
+                                );
+                            }
+                        });
+                    ui.add(
+                        DragValue::new(&mut redondeo.gracia)
+                            .clamp_range(0..=redondeo.minutos)
+                            .suffix(" min"),
+                    )
+                    .on_hover_text("Hasta estos minutos se redondea en sentido contrario");
+                    ui.end_row();
+                }
+            });
+    }
 
-        let t1 = since(
-            from_str(self.datos.fichajes[i % 5].cell.trim(), HM).unwrap_or(zero),
-            zero,
-        )
-        .num_seconds();
-
-        let t2 = since(
-            from_str(self.datos.fichajes[i % 5 + 5].cell.trim(), HM).unwrap_or(zero),
-            zero,
-        )
-        .num_seconds();
-
-        let t3 = since(
-            from_str(self.datos.fichajes[i % 5 + 10].cell.trim(), HM).unwrap_or(zero),
-            zero,
-        )
-        .num_seconds();
-
-        let t4 = since(
-            from_str(self.datos.fichajes[i % 5 + 15].cell.trim(), HM).unwrap_or(zero),
-            zero,
-        )
-        .num_seconds();
-
-        if t1 > t2 && t2 != 0 || t3 > t4 && t4 != 0 || t2 > t3 && t3 != 0 {
-            self.datos.fichajes[i].cell = "      ".to_owned();
-        }
+    fn reglas_descanso(&mut self, ui: &mut Ui) {
+        let descanso = &mut self.datos.reglas.descanso;
+        Grid::new("descanso")
+            .num_columns(3)
+            .min_col_width(110.)
+            .spacing([15., 14.])
+            .show(ui, |ui| {
+                ui.label("");
+                ui.label(RichText::new("Trabajando m\u{e1}s de:").font(FontId::proportional(20.)));
+                ui.add(
+                    DragValue::new(&mut descanso.tras)
+                        .clamp_range(0..=720)
+                        .custom_formatter(|n, _| calculo::horas(n as i64 * 60)),
+                )
+                .on_hover_text("Tiempo seguido, sin contar pausas cortas; 0 = sin descanso");
+                ui.end_row();
+
+                ui.label("");
+                ui.label(RichText::new("Hay que parar:").font(FontId::proportional(20.)));
+                ui.add(
+                    DragValue::new(&mut descanso.minimo)
+                        .clamp_range(1..=180)
+                        .suffix(" min"),
+                )
+                .on_hover_text("Pausa m\u{ed}nima entre Salida Ma\u{f1}ana y Entrada Tarde");
+                ui.end_row();
+
+                ui.label("");
+                ui.label(RichText::new("Si no, se descuenta:").font(FontId::proportional(20.)));
+                ui.add(
+                    DragValue::new(&mut descanso.descuento)
+                        .clamp_range(0..=180)
+                        .suffix(" min"),
+                )
+                .on_hover_text("Menos lo que se haya parado");
+                ui.end_row();
+            });
     }
 
-    #[allow(
-        clippy::too_many_lines,
-        clippy::similar_names,
-        clippy::cast_sign_loss,
-        clippy::cast_possible_truncation
-    )]
-    fn calculo_saldo(&mut self) -> RichText {
-        let from_str = NaiveTime::parse_from_str;
-        let since = NaiveTime::signed_duration_since;
-        let zero = from_str("0000", HM).unwrap();
-        let i = self.x;
+    fn reglas_extras(&mut self, ui: &mut Ui) {
+        let extras = &mut self.datos.reglas.extras;
+        let horas = |n: f64, _| calculo::horas(n as i64 * 60);
+        Grid::new("reglas_extras")
+            .num_columns(3)
+            .min_col_width(110.)
+            .spacing([15., 10.])
+            .show(ui, |ui| {
+                ui.label("");
+                ui.label(RichText::new("A partir de").color(Color32::GRAY));
+                ui.label(RichText::new("Recargo").color(Color32::GRAY));
+                ui.end_row();
+
+                for extra in Extra::TODAS {
+                    let nombre = format!("{}:", extra.nombre());
+                    ui.label(RichText::new(nombre).font(FontId::proportional(20.)));
+                    match extra {
+                        Extra::Diaria => ui
+                            .add(
+                                DragValue::new(&mut extras.diaria)
+                                    .clamp_range(0..=24 * 60)
+                                    .speed(5.)
+                                    .custom_formatter(horas),
+                            )
+                            .on_hover_text("Trabajado en el d\u{ed}a; 0 = sin l\u{ed}mite"),
+                        Extra::Semanal => ui
+                            .add(
+                                DragValue::new(&mut extras.semanal)
+                                    .clamp_range(0..=120 * 60)
+                                    .speed(15.)
+                                    .custom_formatter(horas),
+                            )
+                            .on_hover_text(
+                                "Trabajado en la semana, sin otras extras; 0 = sin l\u{ed}mite",
+                            ),
+                        Extra::Festivo => ui
+                            .checkbox(&mut extras.festivos, "")
+                            .on_hover_text("Todo lo trabajado en un d\u{ed}a marcado Festivo"),
+                        Extra::Nocturna => ui
+                            .horizontal(|ui| {
+                                ui.add(arrastre_hora(&mut extras.noche_desde));
+                                ui.label("-");
+                                ui.add(arrastre_hora(&mut extras.noche_hasta));
+                            })
+                            .response
+                            .on_hover_text("Horario nocturno; iguales = sin nocturnas"),
+                        Extra::FinDeSemana => ui.label("").on_hover_text(
+                            "Todo lo apuntado para el s\u{e1}bado y el domingo en Extras",
+                        ),
+                    };
+                    ui.add(
+                        DragValue::new(extras.recargo_mut(extra))
+                            .clamp_range(0..=400)
+                            .suffix(" %"),
+                    )
+                    .on_hover_text("Lo que vale cada hora: 175 % = 1h 45m");
+                    ui.end_row();
+                }
+            });
+    }
 
-        // +--------------------------------------------------------------------------------------+
-        // +++      Calculo Saldo                                                               +++
-        // +--------------------------------------------------------------------------------------+
+    fn reglas_limites(&mut self, ui: &mut Ui) {
+        let limites = &mut self.datos.reglas.limites;
+        Grid::new("limites")
+            .num_columns(2)
+            .min_col_width(110.)
+            .spacing([15., 8.])
+            .show(ui, |ui| {
+                ui.label(RichText::new("Comprobar:").font(FontId::proportional(20.)));
+                ui.checkbox(&mut limites.activos, "")
+                    .on_hover_text("Estatuto de los Trabajadores; s\u{f3}lo avisa");
+                ui.end_row();
+
+                for (nombre, minutos, ayuda) in [
+                    (
+                        "Descanso entre jornadas:",
+                        &mut limites.descanso_diario,
+                        "M\u{ed}nimo, art. 34.3",
+                    ),
+                    (
+                        "Jornada ordinaria:",
+                        &mut limites.jornada,
+                        "M\u{e1}ximo al d\u{ed}a, art. 34.3; lo que pasa son extraordinarias",
+                    ),
+                    (
+                        "Extraordinarias al a\u{f1}o:",
+                        &mut limites.extras,
+                        "M\u{e1}ximo, art. 35.2",
+                    ),
+                ] {
+                    ui.label(RichText::new(nombre).font(FontId::proportional(20.)));
+                    ui.add(
+                        DragValue::new(minutos)
+                            .clamp_range(0..=200 * 60)
+                            .speed(15.)
+                            .custom_formatter(|n, _| calculo::horas(n as i64 * 60)),
+                    )
+                    .on_hover_text(ayuda);
+                    ui.end_row();
+                }
+            });
+    }
 
-        let t = from_str(self.datos.config[1].cell.trim(), HM).unwrap_or(zero);
-        let time_tardes = since(t, zero).num_seconds();
-        let t = from_str(self.datos.config[2].cell.trim(), HM).unwrap_or(zero);
-        let time_plus = since(t, zero).num_seconds();
-        let t = from_str(self.datos.config[0].cell.trim(), HM).unwrap_or(zero);
-        let time_job = since(t, zero).num_seconds() * 5 + time_plus;
+    fn menu_api(&mut self, ui: &mut Ui, ctx: &Context) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(15.);
+            ui.label(
+                RichText::new("API Local")
+                    .color(Color32::DEBUG_COLOR)
+                    .font(FontId::proportional(24.)),
+            );
+            ui.add_space(20.);
 
-        // -----  Dia  ----------------------------------------------------------------------------
+            let api = &mut self.datos.api;
+            Grid::new("api")
+                .num_columns(3)
+                .min_col_width(80.)
+                .spacing([15., 14.])
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.label(RichText::new("Activa:").font(FontId::proportional(20.)));
+                    ui.checkbox(&mut api.activa, "");
+                    ui.end_row();
+
+                    ui.label("");
+                    ui.label(RichText::new("Puerto:").font(FontId::proportional(20.)));
+                    ui.add(DragValue::new(&mut api.puerto).clamp_range(1024..=65535));
+                    ui.end_row();
+
+                    ui.label("");
+                    ui.label(RichText::new("Token:").font(FontId::proportional(20.)));
+                    ui.label(RichText::new(&api.token).monospace().size(10.));
+                    ui.end_row();
+                });
 
-        let mut times_dia = Vec::new();
+            ui.add_space(15.);
+            ui.label(
+                RichText::new(format!(
+                    "http://127.0.0.1:{}/status  \u{b7}  Authorization: Bearer <token>",
+                    api.puerto
+                ))
+                .color(Color32::GRAY),
+            );
+            ui.add_space(10.);
+            ui.label(RichText::new(&self.mensaje).color(Color32::KHAKI));
+            ui.add_space(10.);
 
-        let fic: Vec<&str> = self
-            .datos
-            .fichajes
-            .iter()
-            .map(|x| x.cell.trim())
-            .collect::<Vec<&str>>();
+            ui.horizontal(|ui| {
+                ui.add_space(50.);
+                let button = Button::new(RichText::new("Aceptar").font(FontId::proportional(17.)));
+                if ui.add_sized([100., 25.], button).clicked() {
+                    self.reiniciar_api(ctx);
+                    if self.mensaje.is_empty() {
+                        self.menu = Menu::Configurar;
+                    }
+                };
 
-        times_dia.push([
-            (&fic[i % 5], &fic[i % 5 + 5]),
-            (&fic[i % 5 + 10], &fic[i % 5 + 15]),
-        ]);
+                ui.add_space(20.);
 
-        let mut dia = times_dia
-            .iter()
-            .map(|day| {
-                day.iter()
-                    .map(|(t1, t2)| {
-                        let start_time = from_str(t1, HM).unwrap_or(zero);
-                        let end_time = from_str(t2, HM).unwrap_or(zero);
-                        match since(end_time, start_time).num_seconds() {
-                            x if x > 0 && start_time != zero => x,
-                            _ => 0,
+                let button =
+                    Button::new(RichText::new("Nuevo token").font(FontId::proportional(17.)));
+                if ui.add_sized([100., 25.], button).clicked() {
+                    self.mensaje = match api::token() {
+                        Ok(token) => {
+                            self.datos.api.token = token;
+                            String::new()
                         }
-                    })
-                    .collect::<Vec<i64>>()
-                    .iter()
-                    .sum::<i64>()
-            })
-            .collect::<Vec<i64>>()
-            .iter()
-            .sum::<i64>();
-
-        if self.datos.fichajes[i % 5].cell.trim() == ""
-            && self.datos.fichajes[i % 5 + 5].cell.trim() == ""
-            && self.datos.fichajes[i % 5 + 10].cell.trim() == ""
-            && self.datos.fichajes[i % 5 + 15].cell.trim() == ""
-        {
-            dia = since(t, zero).num_seconds();
-        }
-
-        // -----  Viernes  ------------------------------------------------------------------------
+                        Err(err) => err.to_string(),
+                    };
+                };
 
-        let times_dia = vec![[(&fic[4], &fic[9]), (&fic[14], &fic[19])]];
+                ui.add_space(20.);
 
-        let viernes = times_dia
-            .iter()
-            .map(|day| {
-                day.iter()
-                    .map(|(t1, t2)| {
-                        let start_time = from_str(t1, HM).unwrap_or(zero);
-                        let end_time = from_str(t2, HM).unwrap_or(zero);
-                        match since(end_time, start_time).num_seconds() {
-                            x if x > 0 && start_time != zero => x,
-                            _ => 0,
-                        }
-                    })
-                    .collect::<Vec<i64>>()
-                    .iter()
-                    .sum::<i64>()
-            })
-            .collect::<Vec<i64>>()
-            .iter()
-            .sum::<i64>();
+                let button = Button::new(RichText::new("Copiar").font(FontId::proportional(17.)));
+                if ui
+                    .add_sized([100., 25.], button)
+                    .on_hover_text("Copiar el token al portapapeles")
+                    .clicked()
+                {
+                    let token = self.datos.api.token.clone();
+                    ui.output_mut(|o| o.copied_text = token);
+                };
 
-        // -----  Tardes  -------------------------------------------------------------------------
+                ui.add_space(20.);
 
-        let mut times_tardes = Vec::new();
+                let button = Button::new(RichText::new("Webhook").font(FontId::proportional(17.)));
+                if ui.add_sized([100., 25.], button).clicked() {
+                    self.mensaje.clear();
+                    self.menu = Menu::Webhook;
+                };
+            });
+        });
+    }
 
-        for i in 0..5 {
-            times_tardes.push([(&fic[i + 10], &fic[i + 15])]);
+    fn menu_webhook(&mut self, ui: &mut Ui) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(15.);
+            ui.label(
+                RichText::new("Webhook")
+                    .color(Color32::DEBUG_COLOR)
+                    .font(FontId::proportional(24.)),
+            );
+            ui.add_space(20.);
+
+            let webhook = &mut self.datos.webhook;
+            Grid::new("webhook")
+                .num_columns(3)
+                .min_col_width(80.)
+                .spacing([15., 12.])
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.label(RichText::new("URL:").font(FontId::proportional(20.)));
+                    ui.add(
+                        TextEdit::singleline(&mut webhook.url)
+                            .hint_text("http://127.0.0.1:8080/horario")
+                            .desired_width(280.),
+                    );
+                    ui.end_row();
+
+                    ui.label("");
+                    ui.label(RichText::new("Fichajes:").font(FontId::proportional(20.)));
+                    ui.checkbox(&mut webhook.fichajes, "Entradas y salidas de hoy");
+                    ui.end_row();
+
+                    ui.label("");
+                    ui.label(RichText::new("Saldo:").font(FontId::proportional(20.)));
+                    ui.checkbox(&mut webhook.saldo, "Cruza el cero");
+                    ui.end_row();
+
+                    ui.label("");
+                    ui.label(RichText::new("Tardes:").font(FontId::proportional(20.)));
+                    ui.checkbox(&mut webhook.tardes, "No llegan el \u{fa}ltimo d\u{ed}a");
+                    ui.end_row();
+                });
+
+            ui.add_space(15.);
+            ui.label(RichText::new(&self.mensaje).color(Color32::KHAKI));
+            ui.add_space(10.);
+
+            ui.horizontal(|ui| {
+                ui.add_space(170.);
+                let button = Button::new(RichText::new("Aceptar").font(FontId::proportional(17.)));
+                if ui.add_sized([100., 25.], button).clicked() {
+                    let url = self.datos.webhook.url.trim();
+                    let resultado = if url.is_empty() {
+                        Ok(())
+                    } else {
+                        webhook::validar(url)
+                    };
+                    match resultado {
+                        Ok(()) => {
+                            self.mensaje.clear();
+                            self.menu = Menu::Api;
+                        }
+                        Err(err) => self.mensaje = err.to_string(),
+                    }
+                };
+
+                ui.add_space(20.);
+
+                let button = Button::new(RichText::new("Probar").font(FontId::proportional(17.)));
+                if ui
+                    .add_sized([100., 25.], button)
+                    .on_hover_text("Enviar un evento de prueba")
+                    .clicked()
+                {
+                    let ahora = Local::now().naive_local();
+                    let carga = webhook::carga(&webhook::Evento::Prueba, &self.datos, ahora);
+                    let ctx = ui.ctx().clone();
+                    self.prueba = Some(webhook::probar(
+                        self.datos.webhook.url.clone(),
+                        carga,
+                        move || ctx.request_repaint(),
+                    ));
+                    self.mensaje = "Enviando\u{2026}".to_owned();
+                };
+            });
+        });
+    }
+
+    /// Arranca o para el servidor según la configuración; sólo lo sirve la instancia principal.
+    fn reiniciar_api(&mut self, ctx: &Context) {
+        self.servidor = None;
+        self.mensaje.clear();
+
+        let api = &mut self.datos.api;
+        if !api.activa || self.instancia.is_none() {
+            return;
+        }
+        if api.token.is_empty() {
+            match api::token() {
+                Ok(token) => api.token = token,
+                Err(err) => {
+                    self.mensaje = err.to_string();
+                    return;
+                }
+            }
+        }
+
+        match api::Servidor::iniciar(api, ctx) {
+            Ok(servidor) => self.servidor = Some(servidor),
+            Err(err) => self.mensaje = format!("Puerto {}: {}", api.puerto, err),
         }
+    }
+
+    fn menu_cerrar(&mut self, ui: &mut Ui) {
+        let resumen = historial::Semana::actual(&self.datos).resumen();
+        let lunes = self.datos.lunes.unwrap_or_else(lunes_actual);
+
+        ui.vertical_centered(|ui| {
+            ui.add_space(15.);
+            ui.label(
+                RichText::new(format!("Cerrar Semana  {}", lunes.format("%d/%m/%Y")))
+                    .color(Color32::DEBUG_COLOR)
+                    .font(FontId::proportional(24.)),
+            );
+            ui.add_space(15.);
+
+            Grid::new("cerrar")
+                .num_columns(5)
+                .min_col_width(100.)
+                .spacing([5., 10.])
+                .show(ui, |ui| {
+                    for item in DAYS_WEEK_NAMES.iter().take(5) {
+                        ui.label(
+                            RichText::new(*item)
+                                .color(Color32::LIGHT_BLUE)
+                                .font(FontId::proportional(18.)),
+                        );
+                    }
+                    ui.end_row();
+
+                    for dia in resumen.dias {
+                        ui.label(
+                            RichText::new(calculo::horas(dia)).font(FontId::proportional(18.)),
+                        );
+                    }
+                    ui.end_row();
+                });
+
+            ui.add_space(15.);
+
+            ui.label(
+                RichText::new(format!(
+                    "Total  [  {}  ]          Objetivo  [  {}  ]",
+                    calculo::horas(resumen.total),
+                    calculo::horas(resumen.objetivo)
+                ))
+                .font(FontId::proportional(20.)),
+            );
+
+            ui.add_space(10.);
+
+            let (color, signo) = color_saldo(resumen.saldo());
+            ui.label(
+                RichText::new(format!(
+                    "{}   [  {}  ]",
+                    signo,
+                    calculo::horas(resumen.saldo())
+                ))
+                .font(FontId::proportional(23.))
+                .color(color),
+            );
+
+            ui.add_space(25.);
+
+            ui.horizontal(|ui| {
+                ui.add_space(150.);
+                let button = Button::new(RichText::new("Cerrar").font(FontId::proportional(17.)));
+                if ui
+                    .add_sized([100., 25.], button)
+                    .on_hover_text("Guardar la semana en el historial y pasar a la siguiente")
+                    .clicked()
+                {
+                    let cerrada = self.datos.lunes.unwrap_or_else(lunes_actual);
+                    historial::cerrar(&mut self.datos, lunes_actual());
+                    self.anotar(auditoria::cambios(
+                        &self.fichados,
+                        &fichajes_vacios(),
+                        cerrada,
+                        Motivo::Cierre,
+                    ));
+                    if let Err(err) = firma::firmar() {
+                        self.fallo("Punto de control", err);
+                    }
+                    self.releer_fichados();
+                    self.x = 0;
+                    self.menu = Menu::Horario;
+                }
+
+                ui.add_space(20.);
+
+                let button = Button::new(RichText::new("Cancelar").font(FontId::proportional(17.)));
+                if ui.add_sized([100., 25.], button).clicked() {
+                    self.menu = Menu::Horario;
+                }
+            });
+        });
+    }
+
+    fn menu_auditoria(&mut self, ui: &mut Ui) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(10.);
+            ui.label(
+                RichText::new("Registro de Fichajes")
+                    .color(Color32::DEBUG_COLOR)
+                    .font(FontId::proportional(24.)),
+            );
+            ui.add_space(10.);
+
+            ScrollArea::vertical().max_height(190.).show(ui, |ui| {
+                Grid::new("auditoria")
+                    .num_columns(5)
+                    .striped(true)
+                    .spacing([15., 4.])
+                    .show(ui, |ui| {
+                        for entrada in self.registro.iter().rev() {
+                            ui.label(entrada.fecha.format("%d/%m/%Y %H:%M:%S").to_string());
+                            ui.label(format!(
+                                "{} {}",
+                                &DAYS_WEEK_NAMES
+                                    [entrada.dia.weekday().num_days_from_monday() as usize][..3],
+                                entrada.dia.format("%d/%m")
+                            ));
+                            ui.label(entrada.fichaje());
+                            ui.label(format!(
+                                "{} \u{2192} {}",
+                                calculo::hora(&entrada.anterior),
+                                calculo::hora(&entrada.nuevo)
+                            ));
+                            ui.label(entrada.motivo.nombre());
+                            ui.end_row();
+                        }
+                    });
+            });
+
+            ui.add_space(10.);
+            ui.label(RichText::new(&self.mensaje).color(Color32::KHAKI));
+            ui.add_space(10.);
+
+            ui.horizontal(|ui| {
+                ui.add_space(72.);
+                let button =
+                    Button::new(RichText::new("Verificar").font(FontId::proportional(17.)));
+                if ui
+                    .add_sized([100., 25.], button)
+                    .on_hover_text("Comprobar la cadena de hashes y los puntos de control")
+                    .clicked()
+                {
+                    self.registro = auditoria::leer();
+                    self.mensaje = match auditoria::verificar(&self.registro) {
+                        auditoria::Verificacion::Rota(n, entrada) => format!(
+                            "Enlace roto en la entrada {} ({})",
+                            n + 1,
+                            entrada.fecha.format("%d/%m/%Y %H:%M:%S")
+                        ),
+                        auditoria::Verificacion::Integra(n) => {
+                            match firma::comprobar(&firma::leer(), &self.registro) {
+                                Some(punto) => format!(
+                                    "Punto de control no v\u{e1}lido ({})",
+                                    punto.fecha.format("%d/%m/%Y %H:%M:%S")
+                                ),
+                                None => format!("Registro \u{ed}ntegro: {n} entradas"),
+                            }
+                        }
+                    };
+                }
+
+                ui.add_space(15.);
+
+                let button = Button::new(RichText::new("Firmar").font(FontId::proportional(17.)));
+                if ui
+                    .add_sized([100., 25.], button)
+                    .on_hover_text("Exportar un punto de control firmado para RR.HH.")
+                    .clicked()
+                {
+                    self.mensaje = match firma::exportar(&carpeta_exportar()) {
+                        Ok(ruta) => ruta.to_string_lossy().into_owned(),
+                        Err(err) => err.to_string(),
+                    };
+                }
+
+                ui.add_space(15.);
+
+                let button = Button::new(RichText::new("Exportar").font(FontId::proportional(17.)));
+                if ui.add_sized([100., 25.], button).clicked() {
+                    self.mensaje = match auditoria::exportar(&carpeta_exportar()) {
+                        Ok(ruta) => ruta.to_string_lossy().into_owned(),
+                        Err(err) => err.to_string(),
+                    };
+                }
+
+                ui.add_space(15.);
+
+                let button = Button::new(RichText::new("Aceptar").font(FontId::proportional(17.)));
+                if ui.add_sized([100., 25.], button).clicked() {
+                    self.menu = Menu::Horario;
+                }
+            });
+        });
+    }
+
+    fn menu_exportar(&mut self, ui: &mut Ui) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(15.);
+            ui.label(
+                RichText::new("Exportar")
+                    .color(Color32::DEBUG_COLOR)
+                    .font(FontId::proportional(24.)),
+            );
+            ui.add_space(20.);
+
+            Grid::new("exportar")
+                .num_columns(3)
+                .min_col_width(110.)
+                .spacing([15., 15.])
+                .show(ui, |ui| {
+                    for (nombre, fecha) in
+                        [("Desde:", &mut self.desde), ("Hasta:", &mut self.hasta)]
+                    {
+                        ui.label("");
+                        ui.label(RichText::new(nombre).font(FontId::proportional(20.)));
+                        ui.add_sized(
+                            [140., 30.],
+                            TextEdit::singleline(fecha)
+                                .hint_text("dd/mm/aaaa")
+                                .font(FontId::proportional(20.)),
+                        );
+                        ui.end_row();
+                    }
+
+                    ui.label("");
+                    ui.label(RichText::new("Ausencias:").font(FontId::proportional(20.)));
+                    ui.checkbox(&mut self.ics_ausencias, "")
+                        .on_hover_text("Un evento de d\u{ed}a completo por ausencia en el ICS");
+                    ui.end_row();
+                });
+
+            ui.add_space(20.);
+
+            let rango = self.rango();
+
+            ui.horizontal(|ui| {
+                ui.add_space(55.);
+                let button = Button::new(RichText::new("CSV").font(FontId::proportional(17.)));
+                if ui
+                    .add_sized([100., 25.], button)
+                    .on_hover_text("Fichajes y totales por d\u{ed}a, con resumen semanal")
+                    .clicked()
+                {
+                    self.mensaje = match rango {
+                        Ok((desde, hasta)) => {
+                            match csv::exportar(&self.datos, desde, hasta, &carpeta_exportar()) {
+                                Ok(ruta) => ruta.to_string_lossy().into_owned(),
+                                Err(err) => err.to_string(),
+                            }
+                        }
+                        Err(_) => "Fecha no v\u{e1}lida".to_owned(),
+                    };
+                }
+
+                ui.add_space(20.);
+
+                let button = Button::new(RichText::new("PDF").font(FontId::proportional(17.)));
+                if ui
+                    .add_sized([100., 25.], button)
+                    .on_hover_text("Registro mensual para firmar, del mes de Desde")
+                    .clicked()
+                {
+                    self.mensaje = match rango {
+                        Ok((desde, _)) => {
+                            match pdf::exportar(&self.datos, desde, &carpeta_exportar()) {
+                                Ok(ruta) => ruta.to_string_lossy().into_owned(),
+                                Err(err) => err.to_string(),
+                            }
+                        }
+                        Err(_) => "Fecha no v\u{e1}lida".to_owned(),
+                    };
+                }
+
+                ui.add_space(20.);
+
+                let button = Button::new(RichText::new("ICS").font(FontId::proportional(17.)));
+                if ui
+                    .add_sized([100., 25.], button)
+                    .on_hover_text("Tramos trabajados como eventos de calendario")
+                    .clicked()
+                {
+                    self.mensaje = match rango {
+                        Ok((desde, hasta)) => match ics::exportar(
+                            &self.datos,
+                            desde,
+                            hasta,
+                            self.ics_ausencias,
+                            &carpeta_exportar(),
+                        ) {
+                            Ok(ruta) => ruta.to_string_lossy().into_owned(),
+                            Err(err) => err.to_string(),
+                        },
+                        Err(_) => "Fecha no v\u{e1}lida".to_owned(),
+                    };
+                }
+
+                ui.add_space(20.);
+
+                let button = Button::new(RichText::new("JSON").font(FontId::proportional(17.)));
+                if ui
+                    .add_sized([100., 25.], button)
+                    .on_hover_text("Copia de todos los datos, sin tener en cuenta las fechas")
+                    .clicked()
+                {
+                    self.mensaje = match copia::exportar(&self.datos, &carpeta_exportar()) {
+                        Ok(ruta) => ruta.to_string_lossy().into_owned(),
+                        Err(err) => err.to_string(),
+                    };
+                }
+            });
+
+            ui.add_space(15.);
+            ui.label(RichText::new(&self.mensaje).color(Color32::KHAKI));
+            ui.add_space(15.);
+
+            let button = Button::new(RichText::new("Aceptar").font(FontId::proportional(17.)));
+            if ui.add_sized([100., 25.], button).clicked() {
+                self.menu = Menu::Horario;
+            };
+        });
+    }
+
+    fn menu_importar(&mut self, ui: &mut Ui) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(10.);
+
+            if self.propuestas.is_empty() {
+                self.importar_columnas(ui);
+            } else {
+                self.importar_propuestas(ui);
+            }
+        });
+    }
+
+    fn importar_columnas(&mut self, ui: &mut Ui) {
+        ui.label(
+            RichText::new("Importar Fichadas")
+                .color(Color32::DEBUG_COLOR)
+                .font(FontId::proportional(24.)),
+        );
+        ui.add_space(10.);
+
+        ui.horizontal(|ui| {
+            ui.add_space(40.);
+            ui.label("Fichero:");
+            ui.add_sized(
+                [440., 20.],
+                TextEdit::singleline(&mut self.fichero)
+                    .hint_text("/ruta/fichadas.csv  \u{2219}  /ruta/copia.json"),
+            );
+        });
+
+        ui.add_space(10.);
+
+        let imp = &mut self.datos.importacion;
+        Grid::new("importar")
+            .num_columns(4)
+            .min_col_width(90.)
+            .spacing([15., 8.])
+            .show(ui, |ui| {
+                ui.label("Separador:");
+                ui.add_sized([120., 20.], TextEdit::singleline(&mut imp.separador));
+                ui.label("Cabecera:");
+                ui.checkbox(&mut imp.cabecera, "");
+                ui.end_row();
+
+                ui.label("Col. Empleado:");
+                ui.add(DragValue::new(&mut imp.col_empleado).clamp_range(0..=50));
+                ui.label("Empleado:");
+                ui.add_sized([120., 20.], TextEdit::singleline(&mut imp.empleado));
+                ui.end_row();
+
+                ui.label("Col. Fecha:");
+                ui.add(DragValue::new(&mut imp.col_fecha).clamp_range(1..=50));
+                ui.label("Col. Hora:");
+                ui.add(DragValue::new(&mut imp.col_hora).clamp_range(0..=50));
+                ui.end_row();
+
+                ui.label("Formato:");
+                ui.add_sized([120., 20.], TextEdit::singleline(&mut imp.formato));
+                ui.label("Col. Sentido:");
+                ui.add(DragValue::new(&mut imp.col_sentido).clamp_range(0..=50));
+                ui.end_row();
+
+                ui.label("Entrada:");
+                ui.add_sized([120., 20.], TextEdit::singleline(&mut imp.entrada));
+                ui.label("Salida:");
+                ui.add_sized([120., 20.], TextEdit::singleline(&mut imp.salida));
+                ui.end_row();
+            });
+
+        ui.add_space(10.);
+        ui.label(RichText::new(&self.mensaje).color(Color32::KHAKI));
+        ui.add_space(10.);
+
+        ui.horizontal(|ui| {
+            ui.add_space(50.);
+            let button = Button::new(RichText::new("Leer").font(FontId::proportional(17.)));
+            if ui
+                .add_sized([100., 25.], button)
+                .on_hover_text("Leer las fichadas del CSV")
+                .clicked()
+            {
+                let fichadas = std::fs::read(self.fichero.trim())
+                    .map_err(|err| err.to_string())
+                    .and_then(|texto| {
+                        csv::leer_fichadas(
+                            &String::from_utf8_lossy(&texto),
+                            &self.datos.importacion,
+                        )
+                    });
+
+                self.mensaje = match fichadas {
+                    Ok(fichadas) => {
+                        self.propuestas =
+                            csv::propuestas(&self.datos, &fichadas, Motivo::Importacion);
+                        format!("{} fichadas, ning\u{fa}n cambio", fichadas.len())
+                    }
+                    Err(err) => err,
+                };
+            }
+
+            ui.add_space(20.);
+
+            let button = Button::new(RichText::new("Combinar").font(FontId::proportional(17.)));
+            if ui
+                .add_sized([100., 25.], button)
+                .on_hover_text("A\u{f1}adir las semanas y ausencias de una copia JSON")
+                .clicked()
+            {
+                self.mensaje = match self.leer_copia() {
+                    Ok(copia) => {
+                        let entradas = copia::combinar(&mut self.datos, copia);
+                        self.anotar(entradas);
+                        "Copia combinada".to_owned()
+                    }
+                    Err(err) => err,
+                };
+            }
+
+            ui.add_space(20.);
+
+            let button = Button::new(RichText::new("Sustituir").font(FontId::proportional(17.)));
+            if ui
+                .add_sized([100., 25.], button)
+                .on_hover_text("Sustituir todos los datos por los de una copia JSON")
+                .clicked()
+            {
+                self.mensaje = match self.leer_copia() {
+                    Ok(copia) => self.sustituir(copia),
+                    Err(err) => err,
+                };
+            }
+
+            ui.add_space(20.);
+
+            let button = Button::new(RichText::new("Cancelar").font(FontId::proportional(17.)));
+            if ui.add_sized([100., 25.], button).clicked() {
+                self.menu = Menu::Horario;
+            }
+        });
+    }
+
+    fn leer_copia(&self) -> Result<Datos, String> {
+        let texto = std::fs::read(self.fichero.trim()).map_err(|err| err.to_string())?;
+        copia::leer(&String::from_utf8_lossy(&texto))
+    }
+
+    /// Sustituye todos los datos, guardando antes una copia de los actuales en la carpeta de datos.
+    fn sustituir(&mut self, mut copia: Datos) -> String {
+        let anterior = data_dir()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "data_dir"))
+            .and_then(|dir| copia::exportar(&self.datos, &dir));
+        let anterior = match anterior {
+            Ok(ruta) => ruta,
+            Err(err) => return err.to_string(),
+        };
+
+        copia.lunes.get_or_insert_with(lunes_actual);
+        copia.api.token = self.datos.api.token.clone();
+        let entradas = copia::cambios(&self.datos, &copia, Motivo::Importacion);
+        self.datos = copia;
+        self.check = self.datos.config[3].cell == "true";
+        self.x = 0;
+        self.anotar(entradas);
+        self.releer_fichados();
+
+        format!("Datos anteriores en {}", anterior.to_string_lossy())
+    }
+
+    fn importar_propuestas(&mut self, ui: &mut Ui) {
+        let conflictos = self.propuestas.iter().filter(|p| p.conflicto()).count();
+        ui.label(
+            RichText::new(format!(
+                "{} cambios  \u{2219}  {} conflictos",
+                self.propuestas.len(),
+                conflictos
+            ))
+            .color(Color32::DEBUG_COLOR)
+            .font(FontId::proportional(24.)),
+        );
+        ui.add_space(10.);
+
+        ScrollArea::vertical().max_height(210.).show(ui, |ui| {
+            Grid::new("propuestas")
+                .num_columns(4)
+                .striped(true)
+                .spacing([15., 4.])
+                .show(ui, |ui| {
+                    for propuesta in self.propuestas.iter_mut() {
+                        let color = if propuesta.conflicto() {
+                            Color32::RED
+                        } else {
+                            Color32::LIGHT_BLUE
+                        };
+
+                        ui.checkbox(&mut propuesta.aceptar, "");
+                        ui.label(format!(
+                            "{} {}",
+                            &DAYS_WEEK_NAMES[propuesta.celda % 5][..3],
+                            propuesta.fecha.format(DMY)
+                        ));
+                        ui.label(FICHAJE_NAMES[propuesta.celda / 5]);
+                        ui.label(
+                            RichText::new(format!(
+                                "{} \u{2192} {}",
+                                calculo::hora(&propuesta.anterior),
+                                calculo::hora(&propuesta.nuevo)
+                            ))
+                            .color(color),
+                        );
+                        ui.end_row();
+                    }
+                });
+        });
+
+        ui.add_space(15.);
+
+        ui.horizontal(|ui| {
+            ui.add_space(170.);
+            let button = Button::new(RichText::new("Aplicar").font(FontId::proportional(17.)));
+            if ui
+                .add_sized([100., 25.], button)
+                .on_hover_text("Guardar los cambios marcados")
+                .clicked()
+            {
+                let lunes = self.datos.lunes.unwrap_or_else(lunes_actual);
+                let (entradas, desordenados) = csv::aplicar(&mut self.datos, &self.propuestas);
+
+                self.mensaje = format!("{} fichajes importados", entradas.len());
+                if !desordenados.is_empty() {
+                    let dias: Vec<String> = desordenados
+                        .iter()
+                        .map(|d| d.format("%d/%m").to_string())
+                        .collect();
+                    self.mensaje = format!(
+                        "{}; sin aplicar por quedar desordenados: {}",
+                        self.mensaje,
+                        dias.join(", ")
+                    );
+                }
+                if let Err(err) = auditoria::registrar(&entradas) {
+                    self.fallo("Auditor\u{ed}a", err);
+                }
+                for entrada in &entradas {
+                    if entrada.dia - Duration::days((entrada.celda % 5) as i64) == lunes {
+                        self.fichados[entrada.celda] = entrada.nuevo.clone();
+                    }
+                }
+                self.propuestas.clear();
+            }
+
+            ui.add_space(20.);
+
+            let button = Button::new(RichText::new("Descartar").font(FontId::proportional(17.)));
+            if ui.add_sized([100., 25.], button).clicked() {
+                self.mensaje.clear();
+                self.propuestas.clear();
+            }
+        });
+    }
+
+    fn menu_ausencias(&mut self, ui: &mut Ui) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(10.);
+
+            if self.ausencias.is_empty() {
+                self.ausencias_palabras(ui);
+            } else {
+                self.ausencias_propuestas(ui);
+            }
+        });
+    }
+
+    fn ausencias_palabras(&mut self, ui: &mut Ui) {
+        ui.label(
+            RichText::new("Importar Ausencias")
+                .color(Color32::DEBUG_COLOR)
+                .font(FontId::proportional(24.)),
+        );
+        ui.add_space(10.);
+
+        ui.horizontal(|ui| {
+            ui.add_space(40.);
+            ui.label("Fichero:");
+            ui.add_sized(
+                [440., 20.],
+                TextEdit::singleline(&mut self.fichero).hint_text("/ruta/calendario.ics"),
+            );
+        });
+
+        ui.add_space(10.);
+
+        let calendario = &mut self.datos.calendario;
+        Grid::new("ausencias")
+            .num_columns(2)
+            .min_col_width(90.)
+            .spacing([15., 8.])
+            .show(ui, |ui| {
+                for (palabras, ausencia) in calendario.palabras.iter_mut().zip(Ausencia::TODAS) {
+                    ui.label(format!("{}:", ausencia.nombre()));
+                    ui.add_sized([360., 20.], TextEdit::singleline(palabras))
+                        .on_hover_text("Palabras clave separadas por comas");
+                    ui.end_row();
+                }
+
+                ui.label("Resto:");
+                ComboBox::from_id_source("por_defecto")
+                    .selected_text(calendario.por_defecto.map_or("Ignorar", Ausencia::nombre))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut calendario.por_defecto, None, "Ignorar");
+                        for ausencia in Ausencia::TODAS {
+                            ui.selectable_value(
+                                &mut calendario.por_defecto,
+                                Some(ausencia),
+                                ausencia.nombre(),
+                            );
+                        }
+                    });
+                ui.end_row();
+            });
+
+        ui.add_space(10.);
+        ui.label(RichText::new(&self.mensaje).color(Color32::KHAKI));
+        ui.add_space(10.);
+
+        ui.horizontal(|ui| {
+            ui.add_space(170.);
+            let button = Button::new(RichText::new("Leer").font(FontId::proportional(17.)));
+            if ui.add_sized([100., 25.], button).clicked() {
+                self.mensaje = match std::fs::read(self.fichero.trim()) {
+                    Ok(texto) => {
+                        self.ausencias = ics::propuestas(
+                            &self.datos,
+                            &String::from_utf8_lossy(&texto),
+                            &self.datos.calendario,
+                        );
+                        "Ninguna ausencia nueva".to_owned()
+                    }
+                    Err(err) => err.to_string(),
+                };
+            }
+
+            ui.add_space(20.);
+
+            let button = Button::new(RichText::new("Cancelar").font(FontId::proportional(17.)));
+            if ui.add_sized([100., 25.], button).clicked() {
+                self.menu = Menu::Horario;
+            }
+        });
+    }
+
+    fn ausencias_propuestas(&mut self, ui: &mut Ui) {
+        let conflictos = self
+            .ausencias
+            .iter()
+            .filter(|p| p.anterior.is_some())
+            .count();
+        ui.label(
+            RichText::new(format!(
+                "{} ausencias  \u{2219}  {} conflictos",
+                self.ausencias.len(),
+                conflictos
+            ))
+            .color(Color32::DEBUG_COLOR)
+            .font(FontId::proportional(24.)),
+        );
+        ui.add_space(10.);
+
+        ScrollArea::vertical().max_height(210.).show(ui, |ui| {
+            Grid::new("propuestas_ics")
+                .num_columns(4)
+                .striped(true)
+                .spacing([15., 4.])
+                .show(ui, |ui| {
+                    for propuesta in self.ausencias.iter_mut() {
+                        let texto = match propuesta.anterior {
+                            Some(anterior) => RichText::new(format!(
+                                "{} \u{2192} {}",
+                                anterior.nombre(),
+                                propuesta.ausencia.nombre()
+                            ))
+                            .color(Color32::RED),
+                            None => RichText::new(propuesta.ausencia.nombre())
+                                .color(Color32::LIGHT_BLUE),
+                        };
+
+                        ui.checkbox(&mut propuesta.aceptar, "");
+                        ui.label(format!(
+                            "{} {}",
+                            &DAYS_WEEK_NAMES
+                                [propuesta.fecha.weekday().num_days_from_monday() as usize][..3],
+                            propuesta.fecha.format(DMY)
+                        ));
+                        ui.label(texto);
+                        ui.label(&propuesta.resumen);
+                        ui.end_row();
+                    }
+                });
+        });
+
+        ui.add_space(15.);
+
+        ui.horizontal(|ui| {
+            ui.add_space(170.);
+            let button = Button::new(RichText::new("Aplicar").font(FontId::proportional(17.)));
+            if ui
+                .add_sized([100., 25.], button)
+                .on_hover_text("Guardar las ausencias marcadas")
+                .clicked()
+            {
+                let n = ics::aplicar(&mut self.datos, &self.ausencias);
+                self.mensaje = format!("{n} ausencias importadas");
+                self.ausencias.clear();
+            }
+
+            ui.add_space(20.);
+
+            let button = Button::new(RichText::new("Descartar").font(FontId::proportional(17.)));
+            if ui.add_sized([100., 25.], button).clicked() {
+                self.mensaje.clear();
+                self.ausencias.clear();
+            }
+        });
+    }
+
+    fn menu_sesiones(&mut self, ui: &mut Ui) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(10.);
+
+            if self.propuestas.is_empty() {
+                self.sesiones_sucesos(ui);
+            } else {
+                self.importar_propuestas(ui);
+            }
+        });
+    }
+
+    /// Fechas Desde y Hasta de Exportar y de la actividad de la sesión.
+    fn rango(&self) -> chrono::ParseResult<(NaiveDate, NaiveDate)> {
+        let desde = NaiveDate::parse_from_str(self.desde.trim(), DMY)?;
+        Ok((desde, NaiveDate::parse_from_str(self.hasta.trim(), DMY)?))
+    }
+
+    /// Sucesos del rango Desde-Hasta, registrados o leídos de `fichero`. Devuelve el mensaje.
+    fn leer_sucesos(&mut self, del_fichero: bool) -> String {
+        let Ok((desde, hasta)) = self.rango() else {
+            return "Fecha no v\u{e1}lida".to_owned();
+        };
+
+        let sucesos = if del_fichero {
+            match std::fs::read(self.fichero.trim()) {
+                Ok(bytes) => sesion::importar(&bytes, self.datos.sesion.usuario.trim()),
+                Err(err) => Err(err.to_string()),
+            }
+        } else {
+            Ok(sesion::leer())
+        };
+
+        match sucesos {
+            Ok(sucesos) => {
+                self.sucesos = sucesos
+                    .into_iter()
+                    .filter(|s| (desde..=hasta).contains(&s.momento.date_naive()))
+                    .collect();
+                format!("{} sucesos", self.sucesos.len())
+            }
+            Err(err) => err,
+        }
+    }
+
+    fn sesiones_sucesos(&mut self, ui: &mut Ui) {
+        ui.label(
+            RichText::new("Actividad de la Sesi\u{f3}n")
+                .color(Color32::DEBUG_COLOR)
+                .font(FontId::proportional(24.)),
+        );
+        ui.add_space(8.);
+
+        ui.horizontal(|ui| {
+            ui.add_space(40.);
+            ui.label("Fichero:");
+            ui.add_sized(
+                [440., 20.],
+                TextEdit::singleline(&mut self.fichero)
+                    .hint_text("/var/log/wtmp  \u{2219}  last -F  \u{2219}  journalctl -o export"),
+            );
+        });
+
+        ui.add_space(8.);
+
+        let config = &mut self.datos.sesion;
+        Grid::new("sesion")
+            .num_columns(4)
+            .min_col_width(90.)
+            .spacing([15., 6.])
+            .show(ui, |ui| {
+                ui.label("Registrar:");
+                ui.checkbox(&mut config.activa, "")
+                    .on_hover_text("Bloqueos y desbloqueos de la pantalla");
+                ui.label("Descanso:");
+                ui.add(
+                    DragValue::new(&mut config.descanso)
+                        .clamp_range(5..=180)
+                        .suffix(" min"),
+                )
+                .on_hover_text("Bloqueo m\u{ed}nimo que cuenta como la comida");
+                ui.end_row();
+
+                ui.label("Desde:");
+                ui.add_sized(
+                    [120., 20.],
+                    TextEdit::singleline(&mut self.desde).hint_text("dd/mm/aaaa"),
+                );
+                ui.label("Hasta:");
+                ui.add_sized(
+                    [120., 20.],
+                    TextEdit::singleline(&mut self.hasta).hint_text("dd/mm/aaaa"),
+                );
+                ui.end_row();
+
+                ui.label("Usuario:");
+                ui.add_sized([120., 20.], TextEdit::singleline(&mut config.usuario))
+                    .on_hover_text("Usuario del equipo en wtmp y en el diario");
+                ui.end_row();
+            });
+
+        ui.add_space(6.);
+
+        ScrollArea::vertical().max_height(80.).show(ui, |ui| {
+            Grid::new("sucesos")
+                .num_columns(3)
+                .striped(true)
+                .spacing([15., 4.])
+                .show(ui, |ui| {
+                    for suceso in &self.sucesos {
+                        let dia = suceso.momento.weekday().num_days_from_monday() as usize;
+                        ui.label(format!(
+                            "{} {}",
+                            &DAYS_WEEK_NAMES[dia][..3],
+                            suceso.momento.format(DMY)
+                        ));
+                        ui.label(suceso.momento.format("%H:%M").to_string());
+                        ui.label(RichText::new(suceso.tipo.nombre()).color(Color32::LIGHT_BLUE));
+                        ui.end_row();
+                    }
+                });
+        });
+
+        ui.add_space(6.);
+        ui.label(RichText::new(&self.mensaje).color(Color32::KHAKI));
+        ui.add_space(6.);
+
+        ui.horizontal(|ui| {
+            ui.add_space(50.);
+            let button = Button::new(RichText::new("Aceptar").font(FontId::proportional(17.)));
+            if ui.add_sized([100., 25.], button).clicked() {
+                self.reiniciar_vigia();
+                if self.mensaje.is_empty() {
+                    self.menu = Menu::Horario;
+                }
+            }
+
+            ui.add_space(20.);
+
+            let button = Button::new(RichText::new("Leer").font(FontId::proportional(17.)));
+            if ui
+                .add_sized([100., 25.], button)
+                .on_hover_text("Sesiones del fichero, o las registradas si no hay fichero")
+                .clicked()
+            {
+                let del_fichero = !self.fichero.trim().is_empty();
+                self.mensaje = self.leer_sucesos(del_fichero);
+            }
+
+            ui.add_space(20.);
+
+            let button = Button::new(RichText::new("Proponer").font(FontId::proportional(17.)));
+            if ui
+                .add_sized([100., 25.], button)
+                .on_hover_text("Fichajes de Desde a Hasta seg\u{fa}n la actividad")
+                .clicked()
+            {
+                self.mensaje = match self.rango() {
+                    Ok((desde, hasta)) => {
+                        let ahora = Local::now().naive_local();
+                        let fichadas = sesion::fichadas(
+                            &self.sucesos,
+                            desde,
+                            hasta,
+                            self.datos.sesion.descanso,
+                            ahora,
+                        );
+                        self.propuestas = csv::propuestas(&self.datos, &fichadas, Motivo::Sesion);
+                        format!("{} fichadas, ning\u{fa}n cambio", fichadas.len())
+                    }
+                    Err(_) => "Fecha no v\u{e1}lida".to_owned(),
+                };
+            }
+
+            ui.add_space(20.);
+
+            let button = Button::new(RichText::new("Cancelar").font(FontId::proportional(17.)));
+            if ui.add_sized([100., 25.], button).clicked() {
+                self.menu = Menu::Horario;
+            }
+        });
+    }
+
+    /// Empieza o deja de registrar la sesión; sólo lo hace la instancia principal.
+    fn reiniciar_vigia(&mut self) {
+        self.mensaje.clear();
+        if !self.datos.sesion.activa || self.instancia.is_none() {
+            self.vigia = None;
+        } else if self.vigia.is_none() {
+            match sesion::Vigia::iniciar() {
+                Ok(vigia) => self.vigia = Some(vigia),
+                Err(err) => self.mensaje = err,
+            }
+        }
+    }
+
+    fn menu_bolsa(&mut self, ui: &mut Ui) {
+        self.calculos
+            .actualizar(&self.datos, Local::now().date_naive());
+        let bolsa = self.calculos.bolsa.clone();
+
+        ui.vertical_centered(|ui| {
+            ui.add_space(15.);
+            ui.label(
+                RichText::new("Bolsa de Horas")
+                    .color(Color32::DEBUG_COLOR)
+                    .font(FontId::proportional(24.)),
+            );
+            ui.add_space(10.);
+
+            let (color, signo) = color_saldo(bolsa.saldo());
+            ui.label(
+                RichText::new(format!(
+                    "{}   [  {}  ]",
+                    signo,
+                    calculo::horas(bolsa.saldo())
+                ))
+                .font(FontId::proportional(23.))
+                .color(color),
+            );
+            ui.add_space(10.);
+
+            let config = &mut self.datos.bolsa;
+            let horas = |n: f64, _| calculo::horas(n as i64 * 60);
+            Grid::new("bolsa")
+                .num_columns(3)
+                .min_col_width(80.)
+                .spacing([15., 8.])
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.label(RichText::new("Tope a favor:").font(FontId::proportional(18.)));
+                    ui.add(
+                        DragValue::new(&mut config.tope_positivo)
+                            .clamp_range(0..=200 * 60)
+                            .speed(15.)
+                            .custom_formatter(horas),
+                    )
+                    .on_hover_text("Lo que pasa se pierde; 0 = sin tope");
+                    ui.end_row();
+
+                    ui.label("");
+                    ui.label(RichText::new("Tope en contra:").font(FontId::proportional(18.)));
+                    ui.add(
+                        DragValue::new(&mut config.tope_negativo)
+                            .clamp_range(0..=200 * 60)
+                            .speed(15.)
+                            .custom_formatter(horas),
+                    )
+                    .on_hover_text("La deuda que pasa no se arrastra; 0 = sin tope");
+                    ui.end_row();
+
+                    ui.label("");
+                    ui.label(RichText::new("Caduca en:").font(FontId::proportional(18.)));
+                    ui.add(
+                        DragValue::new(&mut config.trimestres)
+                            .clamp_range(0..=8)
+                            .suffix(" trimestres"),
+                    )
+                    .on_hover_text(
+                        "Trimestres siguientes al del sobrante para gastarlo; 0 = no caduca",
+                    );
+                    ui.end_row();
+                });
+            ui.add_space(10.);
+
+            // Sobrantes con fecha de caducidad; el primero es el que está en riesgo.
+            for (n, lote) in bolsa
+                .lotes
+                .iter()
+                .filter(|l| l.caduca.is_some())
+                .enumerate()
+                .take(2)
+            {
+                let texto = format!(
+                    "{} de {} caducan el {}",
+                    calculo::horas(lote.segundos),
+                    lote.nombre(),
+                    lote.caduca.unwrap_or_default().format(DMY)
+                );
+                let color = if n == 0 { Color32::GOLD } else { Color32::GRAY };
+                ui.label(RichText::new(texto).color(color));
+            }
+            for (texto, segundos) in [
+                ("Perdidas por el tope", bolsa.perdidas),
+                ("Caducadas", bolsa.caducadas),
+                ("Deuda por encima del tope", bolsa.descubierto),
+            ] {
+                if segundos > 0 {
+                    ui.label(
+                        RichText::new(format!("\u{2716} {}: {}", texto, calculo::horas(segundos)))
+                            .color(Color32::RED),
+                    );
+                }
+            }
+
+            ui.add_space(10.);
+
+            ui.horizontal(|ui| {
+                ui.add_space(110.);
+                let button = Button::new(RichText::new("Aceptar").font(FontId::proportional(17.)));
+                if ui.add_sized([100., 25.], button).clicked() {
+                    self.menu = Menu::Horario;
+                };
+                ui.add_space(20.);
+                let button = Button::new(RichText::new("Extras").font(FontId::proportional(17.)));
+                if ui
+                    .add_sized([100., 25.], button)
+                    .on_hover_text("Bolsa de horas extraordinarias")
+                    .clicked()
+                {
+                    self.menu = Menu::Extras;
+                };
+                ui.add_space(20.);
+                let button = Button::new(RichText::new("A\u{f1}o").font(FontId::proportional(17.)));
+                if ui
+                    .add_sized([100., 25.], button)
+                    .on_hover_text("C\u{f3}mputo anual de horas")
+                    .clicked()
+                {
+                    self.menu = Menu::Anual;
+                };
+            });
+        });
+    }
+
+    fn menu_anual(&mut self, ui: &mut Ui) {
+        self.calculos
+            .actualizar(&self.datos, Local::now().date_naive());
+        let computo = self.calculos.anual.clone();
+
+        ui.vertical_centered(|ui| {
+            ui.add_space(15.);
+            ui.label(
+                RichText::new(format!("C\u{f3}mputo Anual  {}", computo.ejercicio))
+                    .color(Color32::DEBUG_COLOR)
+                    .font(FontId::proportional(24.)),
+            );
+            ui.add_space(15.);
+
+            let config = &mut self.datos.anual;
+            Grid::new("anual")
+                .num_columns(4)
+                .min_col_width(100.)
+                .spacing([15., 8.])
+                .show(ui, |ui| {
+                    ui.label(RichText::new("Horas al a\u{f1}o:").font(FontId::proportional(18.)));
+                    ui.add(
+                        DragValue::new(&mut config.horas)
+                            .clamp_range(0..=3000)
+                            .suffix(" h"),
+                    )
+                    .on_hover_text("Del convenio; 0 = sin c\u{f3}mputo anual");
+                    ui.label(RichText::new("Vacaciones:").font(FontId::proportional(18.)));
+                    ui.add(
+                        DragValue::new(&mut config.vacaciones)
+                            .clamp_range(0..=60)
+                            .suffix(" d\u{ed}as"),
+                    )
+                    .on_hover_text("Laborables al a\u{f1}o, marcadas o no");
+                    ui.end_row();
+
+                    ui.label(RichText::new("Trabajado:").font(FontId::proportional(18.)));
+                    ui.label(calculo::horas(computo.trabajado));
+                    ui.label(RichText::new("Objetivo a hoy:").font(FontId::proportional(18.)));
+                    ui.label(calculo::horas(computo.a_hoy));
+                    ui.end_row();
+
+                    ui.label(RichText::new("Laborables:").font(FontId::proportional(18.)));
+                    ui.label(computo.laborables.to_string());
+                    ui.label(RichText::new("Quedan:").font(FontId::proportional(18.)));
+                    ui.label(computo.quedan.to_string());
+                    ui.end_row();
+                });
+            ui.add_space(15.);
+
+            if computo.objetivo > 0 {
+                let (color, signo) = color_saldo(computo.saldo());
+                ui.label(
+                    RichText::new(format!(
+                        "{}   [  {}  ]",
+                        signo,
+                        calculo::horas(computo.saldo())
+                    ))
+                    .font(FontId::proportional(23.))
+                    .color(color),
+                );
+                ui.add_space(10.);
+                for (texto, saldo) in [
+                    (
+                        "A fin de a\u{f1}o con la jornada configurada:",
+                        computo.previsto,
+                    ),
+                    ("A fin de a\u{f1}o al ritmo de hasta hoy:", computo.ritmo),
+                ] {
+                    ui.label(
+                        RichText::new(format!("{}  {}", texto, estado::saldo_texto(saldo)))
+                            .color(color_saldo(saldo).0),
+                    );
+                }
+            }
+
+            ui.add_space(15.);
+
+            let button = Button::new(RichText::new("Aceptar").font(FontId::proportional(17.)));
+            if ui.add_sized([100., 25.], button).clicked() {
+                self.menu = Menu::Bolsa;
+            };
+        });
+    }
 
-        let tardes = times_tardes
+    fn menu_extras(&mut self, ui: &mut Ui) {
+        let lunes = self.datos.lunes.unwrap_or_else(lunes_actual);
+        let semana = historial::Semana::actual(&self.datos).resumen().extras;
+
+        // Acumulado del año hasta la semana de la rejilla, cada semana con sus recargos.
+        let mut acumulado = [0; 5];
+        let mut compensar = [0; 5];
+        for s in historial::semanas(&self.datos)
             .iter()
-            .map(|tarde_times| {
-                tarde_times
-                    .iter()
-                    .map(|(t1, t2)| {
-                        let start_time = from_str(t1, HM).unwrap_or(zero);
-                        let end_time = from_str(t2, HM).unwrap_or(zero);
-                        match since(end_time, start_time).num_seconds() {
-                            x if x > 0 && start_time != zero => x,
-                            _ => 0,
+            .filter(|s| s.lunes.year() == lunes.year() && s.lunes <= lunes)
+        {
+            let extras = s.resumen().extras;
+            for extra in Extra::TODAS {
+                let n = extra as usize;
+                acumulado[n] += extras[n];
+                compensar[n] += s.reglas.extras.con_recargo(extra, extras[n]);
+            }
+        }
+
+        ui.vertical_centered(|ui| {
+            ui.add_space(15.);
+            ui.label(
+                RichText::new("Horas Extraordinarias")
+                    .color(Color32::DEBUG_COLOR)
+                    .font(FontId::proportional(24.)),
+            );
+            ui.add_space(20.);
+
+            // El sábado y el domingo no tienen casilla en la rejilla: se apunta lo trabajado.
+            let horas = |n: f64, _| calculo::horas(n as i64 * 60);
+            Grid::new("fin_de_semana")
+                .num_columns(4)
+                .spacing([15., 10.])
+                .show(ui, |ui| {
+                    for (dia, nombre) in [(5, "S\u{e1}bado"), (6, "Domingo")] {
+                        let fecha = lunes + Duration::days(dia);
+                        let mut minutos =
+                            self.datos.fin_de_semana.get(&fecha).copied().unwrap_or(0);
+                        let nombre = format!("{} {}:", nombre, fecha.format("%d/%m"));
+                        ui.label(RichText::new(nombre).font(FontId::proportional(20.)));
+                        let respuesta = ui
+                            .add(
+                                DragValue::new(&mut minutos)
+                                    .clamp_range(0..=24 * 60)
+                                    .speed(5.)
+                                    .custom_formatter(horas),
+                            )
+                            .on_hover_text("Trabajado ese d\u{ed}a; cuenta como extraordinaria");
+                        if respuesta.changed() {
+                            match minutos {
+                                0 => self.datos.fin_de_semana.remove(&fecha),
+                                _ => self.datos.fin_de_semana.insert(fecha, minutos),
+                            };
                         }
-                    })
-                    .collect::<Vec<i64>>()
-                    .iter()
-                    .sum::<i64>()
-            })
-            .collect::<Vec<i64>>()
-            .iter()
-            .sum::<i64>();
+                    }
+                    ui.end_row();
+                });
+            ui.add_space(20.);
+
+            Grid::new("extras")
+                .num_columns(5)
+                .min_col_width(90.)
+                .spacing([15., 10.])
+                .show(ui, |ui| {
+                    ui.label("");
+                    for titulo in ["Semana", "A\u{f1}o", "Recargo", "A compensar"] {
+                        ui.label(RichText::new(titulo).color(Color32::GRAY));
+                    }
+                    ui.end_row();
+
+                    for extra in Extra::TODAS {
+                        let n = extra as usize;
+                        ui.label(RichText::new(extra.nombre()).font(FontId::proportional(20.)));
+                        ui.label(calculo::horas(semana[n]));
+                        ui.label(calculo::horas(acumulado[n]));
+                        ui.label(format!("{} %", self.datos.reglas.extras.recargo(extra)));
+                        ui.label(calculo::horas(compensar[n]));
+                        ui.end_row();
+                    }
+
+                    ui.label(RichText::new("Total").font(FontId::proportional(20.)));
+                    ui.label(calculo::horas(semana.iter().sum()));
+                    ui.label(calculo::horas(acumulado.iter().sum()));
+                    ui.label("");
+                    ui.label(
+                        RichText::new(calculo::horas(compensar.iter().sum())).color(Color32::GREEN),
+                    );
+                    ui.end_row();
+                });
+
+            ui.add_space(20.);
+
+            let button = Button::new(RichText::new("Aceptar").font(FontId::proportional(17.)));
+            if ui.add_sized([100., 25.], button).clicked() {
+                self.menu = Menu::Horario;
+            };
+        });
+    }
+
+    fn menu_about(&mut self, ui: &mut Ui) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(50.);
+
+            ui.label(
+                RichText::new(" \u{00a9} 2022 Trapalleiro")
+                    .font(FontId::proportional(24.))
+                    .color(Color32::LIGHT_BLUE),
+            );
+
+            // Called on shutdown, and perhaps at regular intervals.
+            // Allows you to save state.
+
+            // Only called when the “persistence” feature is enabled.
+
+            // On web the state is stored to “Local Storage”.
+            // On native the path is picked using directories_next::ProjectDirs::data_dir which is:
+
+            //     Linux: /home/UserName/.local/share/APPNAME
+            //         macOS: /Users/UserName/Library/Application Support/APPNAME
+            //             Windows: C:\Users\UserName\AppData\Roaming\APPNAME
+
+            //             where APPNAME is what is given to eframe::run_native.
+
+            ui.add_space(20.);
+
+            if let Some(proj_dirs) = ProjectDirs::from("", "", APPNAME) {
+                ui.label(
+                    RichText::new("Datos\n")
+                        .font(FontId::proportional(24.))
+                        .color(Color32::GOLD),
+                );
+                ui.label(
+                    RichText::new(proj_dirs.data_dir().to_string_lossy())
+                        .font(FontId::proportional(20.))
+                        .color(Color32::DEBUG_COLOR),
+                );
+            }
+
+            ui.add_space(50.);
 
-        // -----  Total  --------------------------------------------------------------------------
+            let button = Button::new(RichText::new("Aceptar").font(FontId::proportional(17.)));
+            if ui.add_sized([100., 25.], button).clicked() {
+                self.menu = Menu::Horario;
+            };
+        });
+    }
 
-        let mut times = Vec::new();
+    // --------------------------------------------------------------------------------------------
 
-        for i in 0..5 {
-            times.push([(&fic[i], &fic[i + 5]), (&fic[i + 10], &fic[i + 15])]);
+    /// Añade al registro los fichajes que han cambiado desde la última llamada.
+    fn auditar(&mut self, motivo: Motivo) {
+        let lunes = self.datos.lunes.unwrap_or_else(lunes_actual);
+        let entradas = auditoria::cambios(&self.fichados, &self.datos.fichajes, lunes, motivo);
+        if !entradas.is_empty() {
+            self.anotar(entradas);
         }
+    }
 
-        let mut total = times
-            .iter()
-            .map(|day_times| {
-                day_times
-                    .iter()
-                    .map(|(t1, t2)| {
-                        let start_time = from_str(t1, HM).unwrap_or(zero);
-                        let end_time = from_str(t2, HM).unwrap_or(zero);
-                        match since(end_time, start_time).num_seconds() {
-                            x if x > 0 && start_time != zero => x,
-                            _ => 0,
-                        }
-                    })
-                    .collect::<Vec<i64>>()
-                    .iter()
-                    .sum::<i64>()
-            })
-            .collect::<Vec<i64>>()
+    /// Toma la rejilla como está como punto de partida de `auditar`, tras cambiar de semana.
+    fn releer_fichados(&mut self) {
+        self.fichados = self
+            .datos
+            .fichajes
             .iter()
-            .sum::<i64>();
+            .map(|x| x.cell.trim().to_owned())
+            .collect();
+    }
 
-        for i in 0..5 {
-            if self.datos.fichajes[i % 5].cell.trim() == ""
-                && self.datos.fichajes[i % 5 + 5].cell.trim() == ""
-                && self.datos.fichajes[i % 5 + 10].cell.trim() == ""
-                && self.datos.fichajes[i % 5 + 15].cell.trim() == ""
-            {
-                total += since(t, zero).num_seconds();
+    fn check_fichaje(&mut self) {
+        let i = self.x;
+
+        if !calculo::orden_valido(&self.datos.fichajes, i % 5) {
+            self.datos.fichajes[i].cell = "      ".to_owned();
+        }
+    }
+
+    /// Registra en la auditoría los cambios hechos fuera de la rejilla y avisa a los webhooks.
+    /// Sin terminal, lo que sale por `eprintln!` no lo ve nadie: los fallos de la auditoría y del
+    /// punto de control se quedan en la ventana hasta que se cierran con un clic.
+    fn fallo(&mut self, que: &str, err: impl std::fmt::Display) {
+        self.mensaje = format!("\u{26a0} {que}: {err}");
+    }
+
+    fn anotar(&mut self, entradas: Vec<auditoria::Entrada>) {
+        if let Err(err) = auditoria::registrar(&entradas) {
+            self.fallo("Auditor\u{ed}a", err);
+        }
+
+        if let Some(enviador) = &self.enviador {
+            let ahora = Local::now().naive_local();
+            enviador.avisar(
+                &self.datos,
+                &webhook::fichajes(&entradas, ahora.date()),
+                ahora,
+            );
+        }
+
+        // Las de otras semanas no están en la rejilla.
+        let lunes = self.datos.lunes.unwrap_or_else(lunes_actual);
+        for entrada in entradas {
+            if entrada.dia - Duration::days((entrada.celda % 5) as i64) == lunes {
+                self.fichados[entrada.celda] = entrada.nuevo;
             }
         }
+    }
 
-        // -----  Automático  --------------------------------------------------------------------
+    /// Avisa a los webhooks si el saldo cruza el cero o las tardes no van a llegar.
+    fn vigilar(&mut self, ctx: &Context) {
+        let Some(enviador) = &self.enviador else {
+            return;
+        };
 
-        if self.check {
-            if self.datos.fichajes[4].cell.trim() != "" {
-                self.datos.fichajes[14].cell = "".to_owned();
-                self.datos.fichajes[19].cell = "".to_owned();
+        let ahora = Local::now().naive_local();
+        let eventos = self.vigilante.revisar(&self.datos, ahora);
+        enviador.avisar(&self.datos, &eventos, ahora);
 
-                let ent_vie = since(
-                    from_str(self.datos.fichajes[4].cell.trim(), HM).unwrap_or(zero),
-                    zero,
-                )
-                .num_seconds();
-                let mut sal_vie = ent_vie + (time_job - total + viernes);
+        if !self.datos.webhook.url.is_empty() {
+            ctx.request_repaint_after(std::time::Duration::from_secs(60));
+        }
+    }
 
-                if i == 4 || i == 9 {
-                    dia = sal_vie - ent_vie;
-                }
+    /// Atiende las órdenes del menú de la bandeja y le pasa el estado actual.
+    fn atender_bandeja(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
+        let Some(ordenes) = self.bandeja.as_ref().map(bandeja::Bandeja::ordenes) else {
+            return;
+        };
 
-                if sal_vie > 86400 {
-                    sal_vie -= 86400;
+        let ahora = Local::now().naive_local();
+        for orden in ordenes {
+            match orden {
+                bandeja::Orden::Fichar => {
+                    let aviso = match estado::fichar(&mut self.datos, ahora, Motivo::Bandeja) {
+                        Ok(entrada) => {
+                            self.anotar(vec![entrada]);
+                            String::new()
+                        }
+                        Err(err) => err,
+                    };
+                    if let Some(bandeja) = &mut self.bandeja {
+                        bandeja.aviso = aviso;
+                    }
+                }
+                bandeja::Orden::Mostrar => {
+                    frame.set_visible(true);
+                    frame.set_minimized(false);
                 }
+                bandeja::Orden::Salir => frame.close(),
+            }
+        }
 
-                let sal_vie = NaiveTime::from_num_seconds_from_midnight_opt(sal_vie as u32, 0)
-                    .unwrap_or_default();
+        if let Some(bandeja) = &mut self.bandeja {
+            bandeja.actualizar(&estado::estado(&self.datos, ahora));
+        }
+        ctx.request_repaint_after(std::time::Duration::from_secs(60));
+    }
 
-                self.datos.fichajes[9].cell = sal_vie.format("      %H%M").to_string();
+    /// Recoge el resultado del botón Probar del webhook cuando llega.
+    fn atender_prueba(&mut self) {
+        let Some(resultado) = self.prueba.as_ref().and_then(|r| r.try_recv().ok()) else {
+            return;
+        };
+        self.prueba = None;
+        self.mensaje = match resultado {
+            Ok(()) => "Enviado".to_owned(),
+            Err(err) => err.to_string(),
+        };
+    }
 
-                total = time_job;
-            } else {
-                self.datos.fichajes[9].cell = "".to_owned();
+    /// Atiende las peticiones de la API HTTP.
+    fn atender_api(&mut self) {
+        let Some(peticiones) = self.servidor.as_ref().map(api::Servidor::peticiones) else {
+            return;
+        };
+
+        for (peticion, responder) in peticiones {
+            let (respuesta, entradas) = api::atender(&mut self.datos, &peticion);
+            self.anotar(entradas);
+            let _ = responder.send(respuesta);
+        }
+    }
+
+    /// Ejecuta las órdenes que llegan de otras invocaciones de `horario`.
+    fn atender_instancia(&mut self, frame: &mut eframe::Frame) {
+        let Some(peticiones) = self
+            .instancia
+            .as_ref()
+            .map(instancia::Instancia::peticiones)
+        else {
+            return;
+        };
+
+        for peticion in peticiones {
+            if matches!(
+                peticion.args.first().map(String::as_str),
+                Some("show" | "mostrar")
+            ) {
+                if bandeja::wayland() {
+                    peticion.responder(Err("En Wayland la ventana no se puede mostrar desde \
+                                            otro programa: \u{e1}brala desde la barra de tareas"
+                        .to_owned()));
+                    continue;
+                }
+                frame.set_visible(true);
+                frame.set_minimized(false);
+                peticion.responder(Ok(String::new()));
+                continue;
             }
+
+            let resultado = cli::orden(&mut self.datos, &peticion.args).map(|salida| {
+                self.anotar(salida.entradas);
+                if salida.cierre {
+                    if let Err(err) = firma::firmar() {
+                        self.fallo("Punto de control", err);
+                    }
+                    self.releer_fichados();
+                    self.x = 0;
+                }
+                salida.texto
+            });
+            peticion.responder(resultado);
         }
+    }
+
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    fn calculo_saldo(&mut self) -> RichText {
+        let i = self.x;
+
+        // +--------------------------------------------------------------------------------------+
+        // +++      Calculo Saldo                                                               +++
+        // +--------------------------------------------------------------------------------------+
+
+        // -----  Automático  --------------------------------------------------------------------
+
+        // Una salida que no cabe en el día se deja vacía: si no, la borraría `check_fichaje` en el
+        // siguiente repintado y se volvería a escribir aquí.
+        let entradas = estado::automatico(&mut self.datos);
+        self.anotar(entradas);
+        self.calculos
+            .actualizar(&self.datos, Local::now().date_naive());
+
+        let resumen = historial::Semana::actual(&self.datos).resumen();
+        let time_job = resumen.objetivo;
+        let dia = resumen.dias[i % 5];
 
         // -----  get RichText Saldo  -------------------------------------------------------------
 
         let dia = NaiveTime::from_num_seconds_from_midnight_opt(dia as u32, 0).unwrap_or_default();
 
-        let mut txt_saldo: String = DAYS_WEEK_NAMES[i % 5].to_string();
-        txt_saldo = format!("{} {}", txt_saldo, dia.format(" %H : %M ").to_string());
+        let mut txt_saldo = format!("{} {}", DAYS_WEEK_NAMES[i % 5], dia.format(" %H : %M "));
 
-        let color: Color32;
-
-        match total.cmp(&time_job) {
-            Ordering::Equal => {
-                color = Color32::GOLD;
-                let time = secfmt::from(total as u64);
-                txt_saldo = format!(
-                    "{}          {}          [  {}  ]",
-                    txt_saldo,
-                    CONFIG_SALDO[0],
-                    format!("{}h  {}m", time.days * 24 + time.hours, time.minutes)
-                );
-            }
-            Ordering::Less => {
-                color = Color32::RED;
-                let time = secfmt::from(time_job as u64 - total as u64);
-                txt_saldo = format!(
-                    "{}          {}          [  {}  ]",
-                    txt_saldo,
-                    CONFIG_SALDO[1],
-                    format!("{}h  {}m", time.hours, time.minutes)
-                );
-            }
-            Ordering::Greater => {
-                color = Color32::GREEN;
-                let time = secfmt::from(total as u64 - time_job as u64);
-                txt_saldo = format!(
-                    "{}          {}          [  {}  ]",
-                    txt_saldo,
-                    CONFIG_SALDO[2],
-                    format!("{}h  {}m", time.hours, time.minutes)
-                );
-            }
-        }
+        // Las extraordinarias van a su bolsa, no al saldo.
+        let ordinario = resumen.total - resumen.extra();
+        let (color, signo) = color_saldo(ordinario - time_job);
+        let horas = if ordinario == time_job {
+            calculo::horas(ordinario)
+        } else {
+            calculo::horas(ordinario - time_job)
+        };
+        txt_saldo = format!("{}          {}          [  {}  ]", txt_saldo, signo, horas);
 
-        if time_tardes > tardes {
+        if resumen.objetivo_tardes > resumen.tardes {
             txt_saldo = format!("{} {}", txt_saldo, "\u{2605}");
         }
-        if time_plus > 0 {
+        if resumen.recuperar > 0 {
             txt_saldo = format!("{} {}", txt_saldo, "\u{2691}");
         }
+        if resumen.descansos[i % 5] > 0 {
+            txt_saldo = format!("{} {}", txt_saldo, "\u{2615}");
+        }
+        if resumen.franjas.iter().any(|&f| f > 0) {
+            txt_saldo = format!("{} {}", txt_saldo, "\u{26a0}");
+        }
+        if !self.calculos.avisos.is_empty() {
+            txt_saldo = format!("{} {}", txt_saldo, "\u{2696}");
+        }
+        if resumen.extra() > 0 {
+            txt_saldo = format!("{} {}", txt_saldo, "\u{23f1}");
+        }
 
         RichText::new(txt_saldo)
             .font(FontId::proportional(23.))
@@ -818,19 +2970,34 @@ impl App for Horario {
     }
 
     fn update(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
+        self.atender_bandeja(ctx, frame);
+        self.atender_instancia(frame);
+        self.atender_api();
+        self.atender_prueba();
+        self.vigilar(ctx);
         self.custom_window_frame(ctx, frame, "horario");
     }
 }
 
 /// Show some close/maximize/minimize buttons for the native window.
-fn close_maximize_minimize(ui: &mut Ui, frame: &mut eframe::Frame) {
+fn close_maximize_minimize(ui: &mut Ui, frame: &mut eframe::Frame, a_bandeja: bool) {
     let button_height = 12.0;
 
     let close_response = ui
         .add(Button::new(RichText::new("❌").size(button_height)))
-        .on_hover_text("Cerrar la aplicación");
+        .on_hover_text(match a_bandeja {
+            true if bandeja::wayland() => "Minimizar (Wayland no deja esconder la ventana)",
+            true => "Cerrar a la bandeja",
+            false => "Cerrar la aplicación",
+        });
     if close_response.clicked() {
-        frame.close();
+        if a_bandeja && bandeja::wayland() {
+            frame.set_minimized(true);
+        } else if a_bandeja {
+            frame.set_visible(false);
+        } else {
+            frame.close();
+        }
     }
 
     let minimized_response = ui
@@ -841,11 +3008,74 @@ fn close_maximize_minimize(ui: &mut Ui, frame: &mut eframe::Frame) {
     }
 }
 
-fn get_week() -> String {
-    let dt = Utc::now();
+fn data_dir() -> Option<PathBuf> {
+    ProjectDirs::from("", "", APPNAME).map(|proj_dirs| proj_dirs.data_dir().to_path_buf())
+}
+
+/// Carpeta donde se dejan los ficheros exportados: Descargas, o la carpeta personal.
+fn carpeta_exportar() -> PathBuf {
+    UserDirs::new()
+        .and_then(|dirs| {
+            dirs.download_dir()
+                .map(|dir| dir.to_path_buf())
+                .or_else(|| Some(dirs.home_dir().to_path_buf()))
+        })
+        .or_else(data_dir)
+        .unwrap_or_default()
+}
+
+/// Desde/Hasta de cada día laborable, para las reglas por franjas.
+fn rejilla_franjas(ui: &mut Ui, id: &str, franjas: &mut [reglas::Franja; 5], ayuda: &str) {
+    Grid::new(id)
+        .num_columns(3)
+        .min_col_width(110.)
+        .spacing([15., 6.])
+        .show(ui, |ui| {
+            ui.label("");
+            ui.label(RichText::new("Desde").color(Color32::GRAY));
+            ui.label(RichText::new("Hasta").color(Color32::GRAY));
+            ui.end_row();
+
+            for (nombre, franja) in DAYS_WEEK_NAMES.iter().zip(franjas.iter_mut()) {
+                ui.label(RichText::new(*nombre).font(FontId::proportional(20.)));
+                ui.add(arrastre_hora(&mut franja.desde));
+                ui.add(arrastre_hora(&mut franja.hasta))
+                    .on_hover_text(ayuda);
+                ui.end_row();
+            }
+        });
+}
+
+/// Campo `HH:MM` para una hora guardada en minutos desde medianoche.
+fn arrastre_hora(minutos: &mut u32) -> DragValue<'_> {
+    DragValue::new(minutos)
+        .clamp_range(0..=1439)
+        .speed(5.)
+        .custom_formatter(|n, _| format!("{:02}:{:02}", n as u32 / 60, n as u32 % 60))
+        .custom_parser(|texto| {
+            let hora = NaiveTime::parse_from_str(texto.trim(), "%H:%M").ok()?;
+            Some(f64::from(hora.hour() * 60 + hora.minute()))
+        })
+}
+
+/// Color y signo [ ⛶ | ➖ | ➕ ] de un saldo en segundos.
+fn color_saldo(saldo: i64) -> (Color32, &'static str) {
+    match saldo.cmp(&0) {
+        Ordering::Equal => (Color32::GOLD, CONFIG_SALDO[0]),
+        Ordering::Less => (Color32::RED, CONFIG_SALDO[1]),
+        Ordering::Greater => (Color32::GREEN, CONFIG_SALDO[2]),
+    }
+}
+
+fn lunes_actual() -> NaiveDate {
+    let dt = Local::now().date_naive();
     let num_day_week = dt.weekday().number_from_monday(); // Mon=1, ..., Vie=5, Sat=6, Sun=7
 
-    let lu = dt - Duration::days((num_day_week - 1).into());
+    dt - Duration::days((num_day_week - 1).into())
+}
+
+fn get_week() -> String {
+    let lu = lunes_actual();
     let vi = lu + Duration::days(4);
 
     format!(
@@ -859,6 +3089,29 @@ fn get_week() -> String {
     )
 }
 fn main() -> Result<(), eframe::Error> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        if let Err(err) = cli::ejecutar(&args) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // Una segunda ventana sólo pide a la primera que se muestre.
+    let cerrojo = match instancia::cerrojo() {
+        Ok(Some(cerrojo)) => Ok(cerrojo),
+        Ok(None) => {
+            match instancia::enviar(&["show".to_owned()]) {
+                None => eprintln!("Horario ya est\u{e1} abierto"),
+                Some(Err(err)) => eprintln!("{err}"),
+                Some(Ok(_)) => {}
+            }
+            return Ok(());
+        }
+        Err(err) => Err(err),
+    };
+
     let options = NativeOptions {
         decorated: false,
         transparent: true,
@@ -869,5 +3122,9 @@ fn main() -> Result<(), eframe::Error> {
         ..Default::default()
     };
 
-    run_native(APPNAME, options, Box::new(|cc| Box::new(Horario::new(cc))))
+    run_native(
+        APPNAME,
+        options,
+        Box::new(move |cc| Box::new(Horario::new(cc, cerrojo))),
+    )
 }
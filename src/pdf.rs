@@ -0,0 +1,343 @@
+use std::fmt::Write as _;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::calculo::{hhmm, hora};
+use crate::{historial, legal};
+use crate::{Datos, DAYS_WEEK_NAMES, DMY};
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Informe Mensual PDF                                                                 +++
+// +----------------------------------------------------------------------------------------------+
+
+// PDF mínimo escrito a mano: una página A4 con las fuentes estándar Helvetica, que no hace falta
+// incrustar, y el texto en WinAnsiEncoding para los acentos.
+
+const MONTH_NAMES: [&str; 13] = [
+    "---",
+    "Enero",
+    "Febrero",
+    "Marzo",
+    "Abril",
+    "Mayo",
+    "Junio",
+    "Julio",
+    "Agosto",
+    "Septiembre",
+    "Octubre",
+    "Noviembre",
+    "Diciembre",
+];
+
+const ALTO: f32 = 842.;
+
+/// Avisos legales que caben en la página.
+const AVISOS: usize = 8;
+
+/// Datos de la cabecera del informe, editables en Configurar.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Cabecera {
+    pub empresa: String,
+    pub cif: String,
+    pub centro: String,
+    pub trabajador: String,
+    pub nif: String,
+}
+
+/// Contenido de una página, con `y` medida desde arriba.
+#[derive(Default)]
+struct Pagina {
+    contenido: String,
+}
+
+impl Pagina {
+    fn texto(&mut self, x: f32, y: f32, tam: f32, negrita: bool, texto: &str) {
+        let fuente = if negrita { "F2" } else { "F1" };
+        let _ = writeln!(
+            self.contenido,
+            "BT /{} {} Tf {} {} Td ({}) Tj ET",
+            fuente,
+            tam,
+            x,
+            ALTO - y,
+            escapar(texto)
+        );
+    }
+
+    fn linea(&mut self, x1: f32, y1: f32, x2: f32, y2: f32) {
+        let _ = writeln!(
+            self.contenido,
+            "{} {} m {} {} l S",
+            x1,
+            ALTO - y1,
+            x2,
+            ALTO - y2
+        );
+    }
+
+    fn rect(&mut self, x: f32, y: f32, ancho: f32, alto: f32) {
+        let _ = writeln!(
+            self.contenido,
+            "{} {} {} {} re S",
+            x,
+            ALTO - y - alto,
+            ancho,
+            alto
+        );
+    }
+}
+
+/// Cadena PDF en WinAnsi: los caracteres Latin-1 van en octal, el resto se sustituye por `?`.
+fn escapar(texto: &str) -> String {
+    texto
+        .chars()
+        .map(|c| match c {
+            '(' | ')' | '\\' => format!("\\{c}"),
+            ' '..='~' => c.to_string(),
+            '\u{a0}'..='\u{ff}' => format!("\\{:03o}", c as u32),
+            _ => "?".to_owned(),
+        })
+        .collect()
+}
+
+fn documento(pagina: &Pagina) -> Vec<u8> {
+    let objetos = [
+        "<< /Type /Catalog /Pages 2 0 R >>".to_owned(),
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_owned(),
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 595 842] /Contents 4 0 R \
+         /Resources << /Font << /F1 5 0 R /F2 6 0 R >> >> >>"
+            .to_owned(),
+        format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            pagina.contenido.len(),
+            pagina.contenido
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+            .to_owned(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>"
+            .to_owned(),
+    ];
+
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut posiciones = Vec::new();
+    for (n, objeto) in objetos.iter().enumerate() {
+        posiciones.push(pdf.len());
+        let _ = write!(pdf, "{} 0 obj\n{}\nendobj\n", n + 1, objeto);
+    }
+
+    let xref = pdf.len();
+    let _ = write!(pdf, "xref\n0 {}\n0000000000 65535 f \n", objetos.len() + 1);
+    for posicion in posiciones {
+        let _ = writeln!(pdf, "{posicion:010} 00000 n ");
+    }
+    let _ = write!(
+        pdf,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objetos.len() + 1,
+        xref
+    );
+
+    pdf.into_bytes()
+}
+
+/// Informe del mes de `fecha` con los fichajes, totales y saldo de cada día laborable hasta hoy.
+/// Los totales son lo fichado, sin completar los días vacíos; el saldo es el de la aplicación
+/// (`Semana::parcial`), y lo que no sale de los totales se explica debajo.
+pub fn informe(datos: &Datos, fecha: NaiveDate) -> Vec<u8> {
+    let hoy = chrono::Local::now().date_naive();
+    let cab = &datos.cabecera;
+    let mut p = Pagina::default();
+
+    p.texto(50., 60., 16., true, "Registro de Jornada");
+    p.texto(
+        400.,
+        60.,
+        14.,
+        true,
+        &format!("{} {}", MONTH_NAMES[fecha.month() as usize], fecha.year()),
+    );
+    p.linea(50., 70., 545., 70.);
+
+    p.texto(50., 90., 10., true, "Empresa:");
+    p.texto(110., 90., 10., false, &cab.empresa);
+    p.texto(350., 90., 10., true, "CIF:");
+    p.texto(400., 90., 10., false, &cab.cif);
+    p.texto(50., 105., 10., true, "Centro:");
+    p.texto(110., 105., 10., false, &cab.centro);
+    p.texto(50., 120., 10., true, "Trabajador:");
+    p.texto(110., 120., 10., false, &cab.trabajador);
+    p.texto(350., 120., 10., true, "NIF:");
+    p.texto(400., 120., 10., false, &cab.nif);
+
+    let columnas = [50., 115., 185., 240., 295., 350., 405., 460.];
+    let titulos = [
+        "Fecha", "D\u{ed}a", "Entrada", "Salida", "Entrada", "Salida", "Total", "Ausencia",
+    ];
+
+    let mut y = 150.;
+    for (x, titulo) in columnas.iter().zip(titulos) {
+        p.texto(*x, y, 9., true, titulo);
+    }
+    p.linea(50., y + 5., 545., y + 5.);
+
+    let mut total = 0;
+    let mut objetivo = 0;
+    let mut saldo = 0;
+    let mut recuperar = 0;
+    let mut extras = 0;
+    let mut vacios = 0;
+    let mut ausencias = 0;
+    let mut avisos = Vec::new();
+
+    let primero = fecha.with_day(1).unwrap_or(fecha);
+    let ultimo = (primero + Duration::days(31))
+        .with_day(1)
+        .unwrap_or(primero)
+        - Duration::days(1);
+    let del_mes =
+        |fecha: NaiveDate| fecha.month() == primero.month() && fecha.year() == primero.year();
+    let semanas = historial::semanas(datos);
+    for (n, semana) in semanas.iter().enumerate() {
+        let resumen = semana.resumen();
+        let parcial = semana.parcial(primero, ultimo.min(hoy));
+        saldo += parcial.saldo;
+        recuperar += parcial.recuperar;
+        extras += parcial.extras;
+
+        if (0..5).any(|dia| del_mes(semana.fecha(dia))) {
+            for aviso in legal::avisos(&semanas, n) {
+                let cuando = match aviso.dia() {
+                    Some(dia) if !del_mes(semana.fecha(dia)) => continue,
+                    Some(dia) => semana.fecha(dia).format(DMY).to_string(),
+                    None => format!("Semana del {}", semana.lunes.format(DMY)),
+                };
+                avisos.push(format!("{cuando}: {}", aviso.texto()));
+            }
+        }
+
+        for dia in 0..5 {
+            let fecha = semana.fecha(dia);
+            if !del_mes(fecha) || fecha > hoy {
+                continue;
+            }
+
+            let fichajes = &semana.fichajes;
+            let trabajado = semana.trabajado(dia);
+            let ausencia = semana.ausencia(dia);
+            if !ausencia.is_empty() {
+                ausencias += 1;
+            }
+            if semana.ausencias[dia].is_none() && !ausencia.is_empty() {
+                vacios += 1;
+            }
+
+            y += 16.;
+            let celdas = [
+                fecha.format(DMY).to_string(),
+                DAYS_WEEK_NAMES[dia].to_owned(),
+                hora(&fichajes[dia].cell),
+                hora(&fichajes[dia + 5].cell),
+                hora(&fichajes[dia + 10].cell),
+                hora(&fichajes[dia + 15].cell),
+                hhmm(trabajado),
+                ausencia.to_owned(),
+            ];
+            for (x, celda) in columnas.iter().zip(celdas) {
+                p.texto(*x, y, 9., false, &celda);
+            }
+
+            total += trabajado;
+            objetivo += resumen.objetivos[dia];
+        }
+    }
+
+    y += 8.;
+    p.linea(50., y, 545., y);
+
+    y += 20.;
+    p.texto(50., y, 10., true, "Total trabajado:");
+    p.texto(160., y, 10., false, &hhmm(total));
+    p.texto(300., y, 10., true, "Jornada del mes:");
+    p.texto(410., y, 10., false, &hhmm(objetivo));
+    y += 15.;
+    p.texto(50., y, 10., true, "Saldo:");
+    p.texto(160., y, 10., false, &hhmm(saldo));
+    p.texto(300., y, 10., true, "Ausencias:");
+    p.texto(410., y, 10., false, &ausencias.to_string());
+
+    // El saldo no es Total - Jornada: se explica la diferencia.
+    let mut notas = Vec::new();
+    if vacios > 0 {
+        notas.push(format!(
+            "{vacios} d\u{ed}as sin fichajes cuentan la jornada"
+        ));
+    }
+    if recuperar != 0 {
+        notas.push(format!("Tiempo a Recuperar: -{}", hhmm(recuperar)));
+    }
+    if extras != 0 {
+        notas.push(format!("extraordinarias: -{}", hhmm(extras)));
+    }
+    if !notas.is_empty() {
+        y += 15.;
+        p.texto(
+            50.,
+            y,
+            8.,
+            false,
+            &format!("En el saldo: {}.", notas.join("; ")),
+        );
+    }
+
+    // Límites legales del Estatuto de los Trabajadores
+    if !avisos.is_empty() {
+        y += 25.;
+        p.texto(50., y, 10., true, "Avisos:");
+        for aviso in avisos.iter().take(AVISOS) {
+            y += 13.;
+            p.texto(60., y, 8., false, aviso);
+        }
+        if avisos.len() > AVISOS {
+            y += 13.;
+            p.texto(
+                60.,
+                y,
+                8.,
+                false,
+                &format!("y {} m\u{e1}s", avisos.len() - AVISOS),
+            );
+        }
+    }
+
+    y += 50.;
+    p.rect(50., y, 220., 80.);
+    p.rect(325., y, 220., 80.);
+    p.texto(55., y + 12., 9., false, "Firma del trabajador");
+    p.texto(330., y + 12., 9., false, "Firma y sello de la empresa");
+
+    let ultimo = (primero + Duration::days(31))
+        .with_day(1)
+        .unwrap_or(primero)
+        - Duration::days(1);
+    p.texto(
+        50.,
+        y + 100.,
+        9.,
+        false,
+        &format!("En ______________________, a {}", ultimo.format(DMY)),
+    );
+
+    documento(&p)
+}
+
+/// Escribe el informe del mes de `fecha` en `dir` y devuelve la ruta del fichero.
+pub fn exportar(datos: &Datos, fecha: NaiveDate, dir: &Path) -> io::Result<PathBuf> {
+    let ruta = dir.join(format!("horario-{}.pdf", fecha.format("%Y-%m")));
+    std::fs::write(&ruta, informe(datos, fecha))?;
+
+    Ok(ruta)
+}
@@ -0,0 +1,410 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Reglas de Cómputo                                                                   +++
+// +----------------------------------------------------------------------------------------------+
+
+// Lo que dice el convenio sobre cómo se cuentan los fichajes. La rejilla guarda siempre la hora
+// fichada; las reglas sólo cambian lo que se suma. Cada semana cerrada se queda con las reglas que
+// tenía, como con la configuración.
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Direccion {
+    Arriba,
+    #[default]
+    Abajo,
+    Cercano,
+}
+
+impl Direccion {
+    pub const TODAS: [Direccion; 3] = [Direccion::Arriba, Direccion::Abajo, Direccion::Cercano];
+
+    pub fn nombre(self) -> &'static str {
+        match self {
+            Self::Arriba => "Arriba",
+            Self::Abajo => "Abajo",
+            Self::Cercano => "Al m\u{e1}s cercano",
+        }
+    }
+}
+
+/// Redondeo a múltiplos de `minutos`; 0 = sin redondeo.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Redondeo {
+    pub minutos: u32,
+    pub direccion: Direccion,
+    /// Minutos de margen en contra del redondeo: con 5 minutos hacia arriba y 3 de margen,
+    /// 08:02 se queda en 08:00 y 08:04 pasa a 08:05.
+    pub gracia: u32,
+}
+
+impl Redondeo {
+    pub fn aplicar(&self, segundos: i64) -> i64 {
+        let paso = i64::from(self.minutos) * 60;
+        if paso == 0 {
+            return segundos;
+        }
+
+        let resto = segundos.rem_euclid(paso);
+        let abajo = segundos - resto;
+        let gracia = i64::from(self.gracia) * 60;
+
+        match self.direccion {
+            _ if resto == 0 => segundos,
+            Direccion::Arriba if resto <= gracia => abajo,
+            Direccion::Arriba => abajo + paso,
+            Direccion::Abajo if paso - resto <= gracia => abajo + paso,
+            Direccion::Abajo => abajo,
+            Direccion::Cercano if resto * 2 >= paso => abajo + paso,
+            Direccion::Cercano => abajo,
+        }
+    }
+}
+
+/// Pausa obligatoria: quien trabaja seguido más de `tras` minutos sin parar al menos `minimo`
+/// pierde `descuento` minutos, menos lo que haya parado.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Descanso {
+    /// 0 = sin descanso obligatorio.
+    pub tras: u32,
+    pub minimo: u32,
+    pub descuento: u32,
+}
+
+impl Default for Descanso {
+    fn default() -> Self {
+        Self {
+            tras: 0,
+            minimo: 30,
+            descuento: 30,
+        }
+    }
+}
+
+impl Descanso {
+    /// Segundos a descontar de un día con estos tramos `(entrada, salida)`, en orden.
+    pub fn descuento(&self, tramos: &[(i64, i64)]) -> i64 {
+        let tras = i64::from(self.tras) * 60;
+        if tras == 0 {
+            return 0;
+        }
+        let minimo = i64::from(self.minimo) * 60;
+        let descuento = i64::from(self.descuento) * 60;
+
+        // Bloques de trabajo seguido: una pausa más corta que el mínimo no corta el bloque.
+        let mut total = 0;
+        let (mut trabajado, mut parado) = (0, 0);
+        let mut cierre = |trabajado: i64, parado: i64| {
+            if trabajado > tras {
+                total += (descuento - parado).max(0);
+            }
+        };
+
+        for (n, &(entrada, salida)) in tramos.iter().enumerate() {
+            if n > 0 {
+                let pausa = entrada - tramos[n - 1].1;
+                if pausa < minimo {
+                    parado += pausa;
+                } else {
+                    cierre(trabajado, parado);
+                    (trabajado, parado) = (0, 0);
+                }
+            }
+            trabajado += salida - entrada;
+        }
+        cierre(trabajado, parado);
+
+        total
+    }
+}
+
+/// Franja horaria de un día, en minutos desde medianoche; sin franja si `desde >= hasta`.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Franja {
+    pub desde: u32,
+    pub hasta: u32,
+}
+
+impl Franja {
+    pub fn activa(&self) -> bool {
+        self.desde < self.hasta
+    }
+
+    /// Segundos de la franja que no cubren los tramos `(entrada, salida)`, en orden.
+    pub fn sin_cubrir(&self, tramos: &[(i64, i64)]) -> i64 {
+        if !self.activa() {
+            return 0;
+        }
+        let desde = i64::from(self.desde) * 60;
+        let hasta = i64::from(self.hasta) * 60;
+
+        let cubierto: i64 = tramos
+            .iter()
+            .map(|&(entrada, salida)| (salida.min(hasta) - entrada.max(desde)).max(0))
+            .sum();
+        hasta - desde - cubierto
+    }
+
+    /// Lleva una hora del día (en segundos) dentro de la franja.
+    pub fn recortar(&self, segundos: i64) -> i64 {
+        if !self.activa() {
+            return segundos;
+        }
+        segundos.clamp(i64::from(self.desde) * 60, i64::from(self.hasta) * 60)
+    }
+
+    /// `"HH:MM - HH:MM"`
+    pub fn texto(&self) -> String {
+        let hm = |m: u32| format!("{:02}:{:02}", m / 60, m % 60);
+        format!("{} - {}", hm(self.desde), hm(self.hasta))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Extra {
+    Diaria,
+    Semanal,
+    Festivo,
+    Nocturna,
+    FinDeSemana,
+}
+
+impl Extra {
+    pub const TODAS: [Extra; 5] = [
+        Extra::Diaria,
+        Extra::Semanal,
+        Extra::Festivo,
+        Extra::Nocturna,
+        Extra::FinDeSemana,
+    ];
+
+    pub fn nombre(self) -> &'static str {
+        match self {
+            Self::Diaria => "Diarias",
+            Self::Semanal => "Semanales",
+            Self::Festivo => "Festivos",
+            Self::Nocturna => "Nocturnas",
+            Self::FinDeSemana => "Fin de semana",
+        }
+    }
+}
+
+/// Horas extraordinarias: van a su propia bolsa y no al saldo. Cada tiempo trabajado es de un
+/// solo tipo; primero festivo, después diaria, nocturna y semanal. Lo del sábado y el domingo, que
+/// no tienen casilla en la rejilla, es siempre de fin de semana.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Extras {
+    /// Minutos al día a partir de los que se cuenta extraordinaria; 0 = sin límite.
+    pub diaria: u32,
+    /// Minutos a la semana, sin las extraordinarias de cada día; 0 = sin límite.
+    pub semanal: u32,
+    /// Todo lo trabajado en un día marcado como Festivo.
+    pub festivos: bool,
+    /// Horario nocturno en minutos desde medianoche; puede pasar de medianoche (22:00 - 06:00).
+    /// Sin horario nocturno si `noche_desde == noche_hasta`.
+    pub noche_desde: u32,
+    pub noche_hasta: u32,
+    /// Lo que vale cada hora de los cuatro primeros tipos de `Extra::TODAS`, en %.
+    pub recargos: [u32; 4],
+    /// Fuera de `recargos` para que sigan valiendo las reglas guardadas antes de este tipo.
+    pub recargo_fin_de_semana: u32,
+}
+
+impl Default for Extras {
+    fn default() -> Self {
+        Self {
+            diaria: 0,
+            semanal: 0,
+            festivos: false,
+            noche_desde: 0,
+            noche_hasta: 0,
+            recargos: [100; 4],
+            recargo_fin_de_semana: 100,
+        }
+    }
+}
+
+impl Extras {
+    /// Segundos de los tramos `(entrada, salida)` que caen en el horario nocturno.
+    pub fn nocturno(&self, tramos: &[(i64, i64)]) -> i64 {
+        let desde = i64::from(self.noche_desde) * 60;
+        let hasta = i64::from(self.noche_hasta) * 60;
+        let solape = |(entrada, salida): (i64, i64), (desde, hasta): (i64, i64)| {
+            (salida.min(hasta) - entrada.max(desde)).max(0)
+        };
+
+        tramos
+            .iter()
+            .map(|&tramo| match desde.cmp(&hasta) {
+                Ordering::Equal => 0,
+                Ordering::Less => solape(tramo, (desde, hasta)),
+                Ordering::Greater => solape(tramo, (0, hasta)) + solape(tramo, (desde, 86400)),
+            })
+            .sum()
+    }
+
+    /// Recargo de un tipo, en %.
+    pub fn recargo(&self, extra: Extra) -> u32 {
+        match extra {
+            Extra::FinDeSemana => self.recargo_fin_de_semana,
+            _ => self.recargos[extra as usize],
+        }
+    }
+
+    pub fn recargo_mut(&mut self, extra: Extra) -> &mut u32 {
+        match extra {
+            Extra::FinDeSemana => &mut self.recargo_fin_de_semana,
+            _ => &mut self.recargos[extra as usize],
+        }
+    }
+
+    /// Tiempo compensado de `segundos` extraordinarios de un tipo.
+    pub fn con_recargo(&self, extra: Extra, segundos: i64) -> i64 {
+        segundos * i64::from(self.recargo(extra)) / 100
+    }
+}
+
+/// Límites del Estatuto de los Trabajadores, en minutos. Sólo avisan; no cambian las horas.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Limites {
+    pub activos: bool,
+    /// Entre el final de una jornada y el comienzo de la siguiente (art. 34.3).
+    pub descanso_diario: u32,
+    /// Trabajo efectivo ordinario al día (art. 34.3).
+    pub jornada: u32,
+
+    /// Horas extraordinarias al año (art. 35.2).
+    pub extras: u32,
+}
+
+impl Default for Limites {
+    fn default() -> Self {
+        Self {
+            activos: true,
+            descanso_diario: 12 * 60,
+            jornada: 9 * 60,
+
+            extras: 80 * 60,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Reglas {
+    /// Hora de las entradas (mañana y tarde).
+    pub entradas: Redondeo,
+    /// Hora de las salidas.
+    pub salidas: Redondeo,
+    /// Duración de cada tramo entrada/salida.
+    pub tramos: Redondeo,
+    /// Total del día.
+    pub dias: Redondeo,
+    pub descanso: Descanso,
+    /// Horas de presencia obligatoria de cada día (Lunes..Viernes).
+    pub franjas: [Franja; 5],
+    /// Horario computable de cada día: lo fichado fuera no cuenta.
+    pub ventanas: [Franja; 5],
+    pub limites: Limites,
+    pub extras: Extras,
+}
+
+impl Reglas {
+    /// Hora que cuenta de una entrada del día `dia`: dentro del horario computable y redondeada.
+    pub fn entrada(&self, dia: usize, segundos: i64) -> i64 {
+        self.entradas.aplicar(self.ventanas[dia].recortar(segundos))
+    }
+
+    /// Hora que cuenta de una salida del día `dia`.
+    pub fn salida(&self, dia: usize, segundos: i64) -> i64 {
+        self.salidas.aplicar(self.ventanas[dia].recortar(segundos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const H: i64 = 3600;
+    const M: i64 = 60;
+
+    #[test]
+    fn redondeo_con_gracia() {
+        let redondeo = Redondeo {
+            minutos: 5,
+            direccion: Direccion::Arriba,
+            gracia: 3,
+        };
+        assert_eq!(redondeo.aplicar(8 * H + 2 * M), 8 * H);
+        assert_eq!(redondeo.aplicar(8 * H + 4 * M), 8 * H + 5 * M);
+        assert_eq!(redondeo.aplicar(8 * H + 5 * M), 8 * H + 5 * M);
+
+        let redondeo = Redondeo {
+            direccion: Direccion::Abajo,
+            ..redondeo
+        };
+        assert_eq!(redondeo.aplicar(17 * H + 3 * M), 17 * H + 5 * M);
+        assert_eq!(redondeo.aplicar(17 * H + M), 17 * H);
+
+        let redondeo = Redondeo {
+            minutos: 15,
+            direccion: Direccion::Cercano,
+            gracia: 0,
+        };
+        assert_eq!(redondeo.aplicar(8 * H + 7 * M), 8 * H);
+        assert_eq!(redondeo.aplicar(8 * H + 8 * M), 8 * H + 15 * M);
+        assert_eq!(Redondeo::default().aplicar(8 * H + 7), 8 * H + 7);
+    }
+
+    #[test]
+    fn descanso_descuenta_lo_que_falta() {
+        let descanso = Descanso {
+            tras: 360,
+            minimo: 30,
+            descuento: 30,
+        };
+        // Siete horas seguidas.
+        assert_eq!(descanso.descuento(&[(8 * H, 15 * H)]), 30 * M);
+        // Diez minutos de pausa no cortan el bloque: faltan veinte.
+        assert_eq!(
+            descanso.descuento(&[(8 * H, 12 * H), (12 * H + 10 * M, 15 * H)]),
+            20 * M
+        );
+        // Media hora de pausa sí, y ningún bloque pasa de seis horas.
+        assert_eq!(
+            descanso.descuento(&[(8 * H, 12 * H), (12 * H + 30 * M, 15 * H + 30 * M)]),
+            0
+        );
+        // Justo seis horas.
+        assert_eq!(descanso.descuento(&[(8 * H, 14 * H)]), 0);
+        assert_eq!(Descanso::default().descuento(&[(8 * H, 20 * H)]), 0);
+    }
+
+    #[test]
+    fn nocturno_pasa_de_medianoche() {
+        let extras = Extras {
+            noche_desde: 22 * 60,
+            noche_hasta: 6 * 60,
+            ..Extras::default()
+        };
+        assert_eq!(extras.nocturno(&[(5 * H, 9 * H)]), H);
+        assert_eq!(extras.nocturno(&[(20 * H, 23 * H + 30 * M)]), H + 30 * M);
+        assert_eq!(extras.nocturno(&[(5 * H, 9 * H), (20 * H, 24 * H)]), 3 * H);
+        assert_eq!(extras.nocturno(&[(8 * H, 17 * H)]), 0);
+
+        let extras = Extras {
+            noche_desde: 0,
+            noche_hasta: 6 * 60,
+            ..extras
+        };
+        assert_eq!(extras.nocturno(&[(5 * H, 9 * H), (20 * H, 24 * H)]), H);
+        assert_eq!(Extras::default().nocturno(&[(0, 24 * H)]), 0);
+    }
+}
@@ -0,0 +1,675 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::csv::Sentido;
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Actividad de la Sesión                                                              +++
+// +----------------------------------------------------------------------------------------------+
+
+// Con la ventana abierta, en Linux se escuchan las señales de systemd-logind por D-Bus (bloqueo y
+// desbloqueo de la pantalla, suspensión) y se añaden a `sesiones.jsonl`. Nada de esto toca la
+// rejilla: de los sucesos de cada día salen fichadas propuestas que se revisan antes de aplicarlas.
+// Los días sin registro se pueden rellenar con el historial de sesiones del sistema (más abajo).
+//
+//   primer desbloqueo (o inicio de sesión)  ->  Entrada Mañana
+//   bloqueo más largo, si pasa del descanso  ->  Salida Mañana y Entrada Tarde
+//   último bloqueo                           ->  Salida
+
+const FICHERO: &str = "sesiones.jsonl";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Registrar los sucesos de la sesión mientras la ventana está abierta.
+    pub activa: bool,
+    /// Minutos que tiene que durar un bloqueo para tomarlo por la comida.
+    pub descanso: u32,
+    /// Usuario del equipo cuyas sesiones se importan de wtmp o del diario.
+    pub usuario: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            activa: false,
+            descanso: 30,
+            usuario: std::env::var("USER").unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Tipo {
+    Inicio,
+    Bloqueo,
+    Desbloqueo,
+    Suspension,
+    Reanudacion,
+    Fin,
+}
+
+impl Tipo {
+    pub fn nombre(self) -> &'static str {
+        match self {
+            Self::Inicio => "Inicio de sesi\u{f3}n",
+            Self::Bloqueo => "Bloqueo",
+            Self::Desbloqueo => "Desbloqueo",
+            Self::Suspension => "Suspensi\u{f3}n",
+            Self::Reanudacion => "Reanudaci\u{f3}n",
+            Self::Fin => "Fin de sesi\u{f3}n",
+        }
+    }
+
+    /// Empieza un tramo de actividad.
+    fn activa(self) -> bool {
+        matches!(self, Self::Inicio | Self::Desbloqueo | Self::Reanudacion)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Suceso {
+    pub momento: DateTime<Local>,
+    pub tipo: Tipo,
+}
+
+fn ruta() -> Option<PathBuf> {
+    crate::data_dir().map(|dir| dir.join(FICHERO))
+}
+
+fn registrar(suceso: &Suceso) -> io::Result<()> {
+    let ruta = ruta().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "data_dir"))?;
+    if let Some(dir) = ruta.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(ruta)?;
+    writeln!(file, "{}", serde_json::to_string(suceso)?)
+}
+
+pub fn leer() -> Vec<Suceso> {
+    let Some(file) = ruta().and_then(|ruta| File::open(ruta).ok()) else {
+        return Vec::new();
+    };
+
+    let mut sucesos: Vec<Suceso> = BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|linea| serde_json::from_str(&linea).ok())
+        .collect();
+    sucesos.sort_by_key(|s| s.momento);
+    sucesos
+}
+
+/// Fichadas que salen de los sucesos entre `desde` y `hasta`; el día de `ahora` queda sin salida.
+pub fn fichadas(
+    sucesos: &[Suceso],
+    desde: NaiveDate,
+    hasta: NaiveDate,
+    descanso: u32,
+    ahora: NaiveDateTime,
+) -> Vec<(NaiveDateTime, Option<Sentido>)> {
+    let mut fichadas = Vec::new();
+
+    for fecha in desde.iter_days().take_while(|f| *f <= hasta) {
+        // Tramos de actividad del día; el último puede seguir abierto.
+        let mut tramos: Vec<(NaiveDateTime, Option<NaiveDateTime>)> = Vec::new();
+        for suceso in sucesos {
+            let momento = suceso.momento.naive_local();
+            if momento.date() != fecha {
+                continue;
+            }
+            match tramos.last_mut() {
+                Some((_, fin @ None)) if !suceso.tipo.activa() => *fin = Some(momento),
+                Some((_, None)) => {}
+                _ if suceso.tipo.activa() => tramos.push((momento, None)),
+                _ => {}
+            }
+        }
+
+        let Some(&(entrada, _)) = tramos.first() else {
+            continue;
+        };
+        fichadas.push((entrada, Some(Sentido::Entrada)));
+
+        let comida = tramos
+            .windows(2)
+            .filter_map(|par| Some((par[0].1?, par[1].0)))
+            .max_by_key(|(salida, vuelta)| *vuelta - *salida)
+            .filter(|(salida, vuelta)| *vuelta - *salida >= Duration::minutes(descanso.into()));
+        if let Some((salida, vuelta)) = comida {
+            fichadas.push((salida, Some(Sentido::Salida)));
+            fichadas.push((vuelta, Some(Sentido::Entrada)));
+        }
+
+        // Sin bloqueo al final la sesión se quedó abierta o se apagó sin más. Hoy el último
+        // bloqueo aún puede ser un café.
+        if let Some(&(_, Some(salida))) = tramos.last().filter(|_| fecha != ahora.date()) {
+            fichadas.push((salida, Some(Sentido::Salida)));
+        }
+    }
+
+    fichadas
+}
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Importar Historial de Sesiones                                                      +++
+// +----------------------------------------------------------------------------------------------+
+
+// Para las semanas en que no se fichó, lo único que queda es el registro del equipo. Se aceptan:
+//
+//   - `/var/log/wtmp` tal cual (glibc, 64 bits): entradas y salidas de las sesiones del usuario.
+//   - `last -F` o `last --time-format iso`: lo mismo, en texto.
+//   - `journalctl -o export` o `-o json`: sesiones de systemd-logind y suspensiones.
+//
+// Los sucesos salen en el mismo formato que los registrados, y de ahí las mismas propuestas.
+
+/// Tamaño de `struct utmp` en Linux.
+const UTMP: usize = 384;
+const RUN_LVL: i16 = 1;
+const BOOT_TIME: i16 = 2;
+const USER_PROCESS: i16 = 7;
+const DEAD_PROCESS: i16 = 8;
+
+/// Sucesos de `usuario` en un fichero de cualquiera de los formatos anteriores.
+pub fn importar(bytes: &[u8], usuario: &str) -> Result<Vec<Suceso>, String> {
+    let mut sucesos = if bytes.windows(21).any(|w| w == b"__REALTIME_TIMESTAMP=") {
+        diario(&exportacion(bytes), usuario)
+    } else if bytes.trim_ascii_start().starts_with(b"{") {
+        let entradas = String::from_utf8_lossy(bytes)
+            .lines()
+            .filter_map(|linea| serde_json::from_str::<serde_json::Value>(linea).ok())
+            .map(|json| {
+                let campo = |nombre: &str| json[nombre].as_str().unwrap_or_default().to_owned();
+                (
+                    campo("__REALTIME_TIMESTAMP"),
+                    campo("SYSLOG_IDENTIFIER"),
+                    campo("MESSAGE"),
+                )
+            })
+            .collect::<Vec<_>>();
+        diario(&entradas, usuario)
+    } else if bytes.contains(&0) && bytes.len().is_multiple_of(UTMP) {
+        wtmp(bytes, usuario)
+    } else {
+        last(&String::from_utf8_lossy(bytes), usuario)
+    };
+
+    if sucesos.is_empty() {
+        return Err(format!(
+            "Ninguna sesi\u{f3}n de \"{usuario}\" (wtmp, last -F, journalctl -o export|json)"
+        ));
+    }
+    sucesos.sort_by_key(|s| s.momento);
+    sucesos.dedup();
+    Ok(sucesos)
+}
+
+fn suceso(segundos: i64, micros: u32, tipo: Tipo) -> Option<Suceso> {
+    let momento = NaiveDateTime::from_timestamp_opt(segundos, micros * 1000)?;
+    Some(Suceso {
+        momento: Local.from_utc_datetime(&momento),
+        tipo,
+    })
+}
+
+/// Cadena C de longitud fija.
+fn cadena(bytes: &[u8]) -> String {
+    let fin = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..fin]).into_owned()
+}
+
+/// Con varias sesiones a la vez (una terminal dentro de la sesión gráfica) la actividad empieza
+/// con la primera que se abre y termina con la última que se cierra.
+fn wtmp(bytes: &[u8], usuario: &str) -> Vec<Suceso> {
+    let mut sucesos = Vec::new();
+    // Terminales con una sesión del usuario abierta.
+    let mut abiertas = Vec::new();
+
+    for registro in bytes.chunks_exact(UTMP) {
+        let clase = i16::from_le_bytes([registro[0], registro[1]]);
+        let linea = cadena(&registro[8..40]);
+        let nombre = cadena(&registro[44..76]);
+        let segundos =
+            i32::from_le_bytes([registro[340], registro[341], registro[342], registro[343]]);
+
+        let tipo = match clase {
+            USER_PROCESS if nombre == usuario => {
+                abiertas.push(linea);
+                if abiertas.len() > 1 {
+                    continue;
+                }
+                Tipo::Inicio
+            }
+            DEAD_PROCESS if abiertas.contains(&linea) => {
+                abiertas.retain(|l| *l != linea);
+                if !abiertas.is_empty() {
+                    continue;
+                }
+                Tipo::Fin
+            }
+            // Apagado: cierra todo lo abierto.
+            RUN_LVL if nombre == "shutdown" && !abiertas.is_empty() => {
+                abiertas.clear();
+                Tipo::Fin
+            }
+            // Arranque sin apagado previo: no se sabe cuándo terminaron.
+            BOOT_TIME => {
+                abiertas.clear();
+                continue;
+            }
+            _ => continue,
+        };
+        sucesos.extend(suceso(segundos.into(), 0, tipo));
+    }
+
+    sucesos
+}
+
+/// Fechas de una línea de `last`, con `-F` (`Mon Oct 12 08:01:23 2026`) o en ISO 8601.
+fn fechas(palabras: &[&str]) -> Vec<DateTime<Local>> {
+    let mut fechas = Vec::new();
+    let mut i = 0;
+
+    while i < palabras.len() {
+        if let Ok(fecha) = DateTime::parse_from_rfc3339(palabras[i]) {
+            fechas.push(fecha.with_timezone(&Local));
+            i += 1;
+        } else if let Some(fecha) = palabras
+            .get(i..i + 5)
+            .and_then(|p| NaiveDateTime::parse_from_str(&p.join(" "), "%a %b %e %H:%M:%S %Y").ok())
+        {
+            fechas.extend(Local.from_local_datetime(&fecha).earliest());
+            i += 5;
+        } else {
+            i += 1;
+        }
+    }
+
+    fechas
+}
+
+/// `(10:02)` o `(1+10:02)`.
+fn duracion(palabra: &str) -> Option<Duration> {
+    let texto = palabra.strip_prefix('(')?.strip_suffix(')')?;
+    let (dias, hora) = texto.split_once('+').unwrap_or(("0", texto));
+    let (horas, minutos) = hora.split_once(':')?;
+    Some(
+        Duration::days(dias.parse().ok()?)
+            + Duration::hours(horas.parse().ok()?)
+            + Duration::minutes(minutos.parse().ok()?),
+    )
+}
+
+fn last(texto: &str, usuario: &str) -> Vec<Suceso> {
+    let mut sesiones = Vec::new();
+
+    for linea in texto.lines() {
+        let palabras: Vec<&str> = linea.split_whitespace().collect();
+        if palabras.first() != Some(&usuario) {
+            continue;
+        }
+
+        let fechas = fechas(&palabras);
+        let Some(&inicio) = fechas.first() else {
+            continue;
+        };
+
+        // `- down` no trae la hora, pero sí la duración; `crash` y `still logged in`, nada.
+        let fin = fechas.get(1).copied().or_else(|| {
+            palabras.contains(&"down").then_some(())?;
+            Some(inicio + duracion(palabras.last()?)?)
+        });
+        sesiones.push((inicio, fin));
+    }
+
+    // Las sesiones que se solapan son un solo tramo, como en `wtmp`. Una sin final cierra el
+    // tramo: no se sabe hasta cuándo duró.
+    sesiones.sort_by_key(|(inicio, _)| *inicio);
+    let mut tramos: Vec<(DateTime<Local>, Option<DateTime<Local>>)> = Vec::new();
+    for (inicio, fin) in sesiones {
+        match tramos.last_mut() {
+            Some((_, Some(ultimo))) if inicio <= *ultimo => {
+                if let Some(fin) = fin {
+                    *ultimo = fin.max(*ultimo);
+                }
+            }
+            _ => tramos.push((inicio, fin)),
+        }
+    }
+
+    let mut sucesos = Vec::new();
+    for (inicio, fin) in tramos {
+        sucesos.push(Suceso {
+            momento: inicio,
+            tipo: Tipo::Inicio,
+        });
+        if let Some(momento) = fin {
+            sucesos.push(Suceso {
+                momento,
+                tipo: Tipo::Fin,
+            });
+        }
+    }
+
+    sucesos
+}
+
+/// Entradas de `journalctl -o export` como `(__REALTIME_TIMESTAMP, SYSLOG_IDENTIFIER, MESSAGE)`.
+/// Los campos binarios van como nombre, longitud en 64 bits y contenido.
+fn exportacion(bytes: &[u8]) -> Vec<(String, String, String)> {
+    let mut entradas = Vec::new();
+    let mut campos: [String; 3] = Default::default();
+    let mut resto = bytes;
+
+    while !resto.is_empty() {
+        let fin = resto
+            .iter()
+            .position(|&b| b == b'\n')
+            .unwrap_or(resto.len());
+        let linea = &resto[..fin];
+        resto = resto.get(fin + 1..).unwrap_or_default();
+
+        if linea.is_empty() {
+            entradas.push(std::mem::take(&mut campos).into());
+            continue;
+        }
+
+        let (nombre, valor) = match linea.iter().position(|&b| b == b'=') {
+            Some(igual) => (&linea[..igual], linea[igual + 1..].to_vec()),
+            None => {
+                let Some(largo) = resto.get(..8) else { break };
+                let largo = u64::from_le_bytes(largo.try_into().unwrap_or_default()) as usize;
+                let Some(valor) = resto.get(8..8 + largo) else {
+                    break;
+                };
+                let valor = valor.to_vec();
+                resto = resto.get(8 + largo + 1..).unwrap_or_default();
+                (linea, valor)
+            }
+        };
+
+        let posicion = match nombre {
+            b"__REALTIME_TIMESTAMP" => 0,
+            b"SYSLOG_IDENTIFIER" => 1,
+            b"MESSAGE" => 2,
+            _ => continue,
+        };
+        campos[posicion] = String::from_utf8_lossy(&valor).into_owned();
+    }
+    if !campos[0].is_empty() {
+        entradas.push(campos.into());
+    }
+
+    entradas
+}
+
+/// Sesiones de logind y suspensiones del diario.
+fn diario(entradas: &[(String, String, String)], usuario: &str) -> Vec<Suceso> {
+    let mut sucesos = Vec::new();
+    // Sesiones abiertas del usuario, por número.
+    let mut abiertas = Vec::new();
+
+    for (momento, programa, mensaje) in entradas {
+        let Ok(micros) = momento.parse::<i64>() else {
+            continue;
+        };
+        // Los mensajes nuevos llevan comillas: "New session '3' of user 'juan' with class…".
+        let palabras: Vec<&str> = mensaje
+            .split_whitespace()
+            .map(|p| p.trim_matches(|c| c == '\'' || c == '.'))
+            .collect();
+
+        let tipo = match (programa.as_str(), palabras.as_slice()) {
+            // Como en `wtmp`, sólo la primera sesión que se abre y la última que se cierra.
+            ("systemd-logind", ["New", "session", n, "of", "user", u, ..]) if *u == usuario => {
+                abiertas.push(n.to_string());
+                if abiertas.len() > 1 {
+                    continue;
+                }
+                Tipo::Inicio
+            }
+            (
+                "systemd-logind",
+                ["Removed", "session", n, ..] | ["Session", n, "logged", "out", ..],
+            ) if abiertas.iter().any(|a| a == n) => {
+                abiertas.retain(|a| a != n);
+                if !abiertas.is_empty() {
+                    continue;
+                }
+                Tipo::Fin
+            }
+            ("systemd-sleep", ["Entering", "sleep", ..] | ["Performing", "sleep", ..]) => {
+                Tipo::Suspension
+            }
+            ("systemd-sleep", ["System", "returned", "from", "sleep", ..]) => Tipo::Reanudacion,
+            _ => continue,
+        };
+        sucesos.extend(suceso(
+            micros.div_euclid(1_000_000),
+            micros.rem_euclid(1_000_000) as u32,
+            tipo,
+        ));
+    }
+
+    sucesos
+}
+
+/// Hilo que escucha a logind mientras viva; al soltarlo deja de registrar.
+#[derive(Debug)]
+pub struct Vigia {
+    activa: Arc<AtomicBool>,
+}
+
+impl Drop for Vigia {
+    fn drop(&mut self) {
+        self.activa.store(false, Ordering::Relaxed);
+    }
+}
+
+impl Vigia {
+    pub fn iniciar() -> Result<Self, String> {
+        #[cfg(target_os = "linux")]
+        {
+            let activa = Arc::new(AtomicBool::new(true));
+            logind::escuchar(Arc::clone(&activa)).map_err(|err| format!("logind: {err}"))?;
+            Ok(Self { activa })
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            Err("S\u{f3}lo disponible en Linux".to_owned())
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod logind {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use chrono::Local;
+    use zbus::blocking::{Connection, MessageIterator};
+    use zbus::zvariant::{OwnedObjectPath, OwnedValue};
+
+    use super::{registrar, suceso, Suceso, Tipo};
+
+    const LOGIND: &str = "org.freedesktop.login1";
+
+    /// Busca la sesión gráfica del usuario, apunta cuándo empezó y deja un hilo escuchando.
+    pub fn escuchar(activa: Arc<AtomicBool>) -> zbus::Result<()> {
+        let conexion = Connection::system()?;
+        let sesion: OwnedObjectPath = conexion
+            .call_method(
+                Some(LOGIND),
+                "/org/freedesktop/login1",
+                Some("org.freedesktop.login1.Manager"),
+                "GetSession",
+                &("auto"),
+            )?
+            .body()
+            .deserialize()?;
+
+        let inicio: OwnedValue = conexion
+            .call_method(
+                Some(LOGIND),
+                sesion.as_str(),
+                Some("org.freedesktop.DBus.Properties"),
+                "Get",
+                &("org.freedesktop.login1.Session", "Timestamp"),
+            )?
+            .body()
+            .deserialize()?;
+        if let Some(suceso) = u64::try_from(inicio).ok().and_then(|us| {
+            suceso(
+                (us / 1_000_000) as i64,
+                (us % 1_000_000) as u32,
+                Tipo::Inicio,
+            )
+        }) {
+            if !super::leer().contains(&suceso) {
+                let _ = registrar(&suceso);
+            }
+        }
+
+        let mensajes = MessageIterator::for_match_rule(
+            format!("type='signal',sender='{LOGIND}'").as_str(),
+            &conexion,
+            None,
+        )?;
+
+        std::thread::spawn(move || {
+            let mut ultimo = None;
+            for mensaje in mensajes.flatten() {
+                if !activa.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                let cabecera = mensaje.header();
+                let propia = cabecera.path().map(|p| p.as_str()) == Some(sesion.as_str());
+                let tipo = match cabecera.member().map(|m| m.as_str()) {
+                    Some("Lock") if propia => Some(Tipo::Bloqueo),
+                    Some("Unlock") if propia => Some(Tipo::Desbloqueo),
+                    // Lo que pone el salvapantallas al bloquear y desbloquear.
+                    Some("PropertiesChanged") if propia => mensaje
+                        .body()
+                        .deserialize::<(String, HashMap<String, OwnedValue>, Vec<String>)>()
+                        .ok()
+                        .and_then(|(_, cambios, _)| cambios.get("LockedHint").cloned())
+                        .and_then(|valor| bool::try_from(valor).ok())
+                        .map(|bloqueada| {
+                            if bloqueada {
+                                Tipo::Bloqueo
+                            } else {
+                                Tipo::Desbloqueo
+                            }
+                        }),
+                    Some("PrepareForSleep") => {
+                        mensaje.body().deserialize::<bool>().ok().map(|dormir| {
+                            if dormir {
+                                Tipo::Suspension
+                            } else {
+                                Tipo::Reanudacion
+                            }
+                        })
+                    }
+                    _ => None,
+                };
+
+                // `Lock` y `LockedHint` suelen llegar juntos.
+                if let Some(tipo) = tipo.filter(|t| Some(*t) != ultimo) {
+                    ultimo = Some(tipo);
+                    let suceso = Suceso {
+                        momento: Local::now(),
+                        tipo,
+                    };
+                    if let Err(err) = registrar(&suceso) {
+                        eprintln!("sesiones: {err}");
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tipos(sucesos: &[Suceso]) -> Vec<(String, Tipo)> {
+        sucesos
+            .iter()
+            .map(|s| (s.momento.format("%d %H:%M").to_string(), s.tipo))
+            .collect()
+    }
+
+    #[test]
+    fn last_une_las_sesiones_que_se_solapan() {
+        let texto = "\
+ana      pts/1        :0               Mon Oct 12 09:30:00 2026 - Mon Oct 12 13:00:00 2026  (03:30)
+ana      tty2         tty2             Mon Oct 12 07:58:00 2026 - Mon Oct 12 14:02:00 2026  (06:04)
+ana      tty2         tty2             Mon Oct 12 15:00:00 2026 - Mon Oct 12 18:00:00 2026  (03:00)
+ana      pts/2        :0               Mon Oct 12 17:00:00 2026 - Mon Oct 12 18:30:00 2026  (01:30)
+luis     tty3         tty3             Mon Oct 12 06:00:00 2026 - Mon Oct 12 20:00:00 2026  (14:00)
+ana      tty2         tty2             Tue Oct 13 08:00:00 2026   still logged in
+
+wtmp begins Thu Oct  1 08:00:00 2026";
+
+        assert_eq!(
+            tipos(&importar(texto.as_bytes(), "ana").unwrap()),
+            [
+                ("12 07:58", Tipo::Inicio),
+                ("12 14:02", Tipo::Fin),
+                ("12 15:00", Tipo::Inicio),
+                ("12 18:30", Tipo::Fin),
+                ("13 08:00", Tipo::Inicio),
+            ]
+            .map(|(momento, tipo)| (momento.to_owned(), tipo))
+        );
+        assert!(importar(texto.as_bytes(), "eva").is_err());
+    }
+
+    #[test]
+    fn wtmp_une_las_sesiones_que_se_solapan() {
+        let registro = |clase: i16, linea: &str, nombre: &str, segundos: i32| {
+            let mut registro = vec![0; UTMP];
+            registro[..2].copy_from_slice(&clase.to_le_bytes());
+            registro[8..8 + linea.len()].copy_from_slice(linea.as_bytes());
+            registro[44..44 + nombre.len()].copy_from_slice(nombre.as_bytes());
+            registro[340..344].copy_from_slice(&segundos.to_le_bytes());
+            registro
+        };
+        let bytes = [
+            registro(USER_PROCESS, "tty2", "ana", 1_000),
+            registro(USER_PROCESS, "pts/1", "ana", 2_000),
+            registro(DEAD_PROCESS, "tty2", "", 3_000),
+            registro(DEAD_PROCESS, "pts/1", "", 4_000),
+            registro(USER_PROCESS, "tty2", "ana", 5_000),
+            registro(RUN_LVL, "~", "shutdown", 6_000),
+        ]
+        .concat();
+
+        let sucesos = importar(&bytes, "ana").unwrap();
+        assert_eq!(
+            sucesos
+                .iter()
+                .map(|s| (s.momento.timestamp(), s.tipo))
+                .collect::<Vec<_>>(),
+            [
+                (1_000, Tipo::Inicio),
+                (4_000, Tipo::Fin),
+                (5_000, Tipo::Inicio),
+                (6_000, Tipo::Fin),
+            ]
+        );
+    }
+}
@@ -0,0 +1,361 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use chrono::{Duration as Dias, Local, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::auditoria::Entrada;
+use crate::historial::Semana;
+use crate::{calculo, estado, Datos, FICHAJE_NAMES};
+
+// +----------------------------------------------------------------------------------------------+
+// +++      Webhooks                                                                            +++
+// +----------------------------------------------------------------------------------------------+
+
+// POST con un JSON a una URL `http://` (pensado para un servicio local, sin TLS) al fichar hoy
+// una entrada o una salida, cuando el saldo de la semana cruza el cero y cuando el último día
+// laborable de la semana no llegan las horas de Obligatorio Tardes:
+//
+//   {
+//     "evento":  "entrada" | "salida" | "saldo_positivo" | "saldo_negativo" | "tardes_en_riesgo",
+//     "momento": "2026-10-18T10:00:00+02:00",
+//     "fichaje": { "fecha": "AAAA-MM-DD", "nombre": "Entrada Mañana", "hora": "08:00" },
+//     "estado":  { "dentro", "hoy", "saldo", "tardes", "salida" },
+//     "resumen": { "total", "objetivo", "saldo", "tardes", "objetivo_tardes", "recuperar" }
+//   }
+//
+// Los tiempos van en segundos; `fichaje` sólo en entrada y salida. Si el servicio no contesta 2xx
+// se reintenta hasta 5 veces, esperando 1, 2, 4 y 8 segundos, mientras siga abierto el proceso.
+
+const INTENTOS: u32 = 5;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub url: String,
+    pub fichajes: bool,
+    pub saldo: bool,
+    pub tardes: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            fichajes: true,
+            saldo: true,
+            tardes: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Evento {
+    Fichaje {
+        fecha: NaiveDate,
+        celda: usize,
+        hora: String,
+    },
+    /// El saldo pasa a ser positivo (`true`) o negativo.
+    Saldo(bool),
+    TardesEnRiesgo,
+    /// Enviado desde la pantalla de configuración.
+    Prueba,
+}
+
+impl Evento {
+    fn nombre(&self) -> &'static str {
+        match self {
+            Self::Fichaje { celda, .. } if (celda / 5).is_multiple_of(2) => "entrada",
+            Self::Fichaje { .. } => "salida",
+            Self::Saldo(true) => "saldo_positivo",
+            Self::Saldo(false) => "saldo_negativo",
+            Self::TardesEnRiesgo => "tardes_en_riesgo",
+            Self::Prueba => "prueba",
+        }
+    }
+
+    fn activo(&self, config: &Config) -> bool {
+        match self {
+            Self::Fichaje { .. } => config.fichajes,
+            Self::Saldo(_) => config.saldo,
+            Self::TardesEnRiesgo => config.tardes,
+            Self::Prueba => true,
+        }
+    }
+}
+
+/// Fichajes de `hoy` entre los cambios auditados; borrar una casilla no es fichar.
+pub fn fichajes(entradas: &[Entrada], hoy: NaiveDate) -> Vec<Evento> {
+    entradas
+        .iter()
+        .filter(|e| e.dia == hoy && !e.nuevo.is_empty())
+        .map(|e| Evento::Fichaje {
+            fecha: e.dia,
+            celda: e.celda,
+            hora: calculo::hora(&e.nuevo),
+        })
+        .collect()
+}
+
+/// `ahora` con la zona horaria local; sin ella si esa hora no existe (cambio de hora).
+fn momento(ahora: NaiveDateTime) -> String {
+    match ahora.and_local_timezone(Local).earliest() {
+        Some(momento) => momento.to_rfc3339(),
+        None => ahora.format("%Y-%m-%dT%H:%M:%S").to_string(),
+    }
+}
+
+pub fn carga(evento: &Evento, datos: &Datos, ahora: NaiveDateTime) -> Value {
+    let estado = estado::estado(datos, ahora);
+    let resumen = Semana::actual(datos).resumen();
+
+    let mut carga = json!({
+        "evento": evento.nombre(),
+        "momento": momento(ahora),
+        "estado": {
+            "dentro": estado.dentro,
+            "hoy": estado.hoy,
+            "saldo": estado.saldo,
+            "tardes": estado.tardes,
+            "salida": estado.salida.map(|s| s.format("%H:%M").to_string()),
+        },
+        "resumen": {
+            "total": resumen.total,
+            "objetivo": resumen.objetivo,
+            "saldo": resumen.saldo(),
+            "tardes": resumen.tardes,
+            "objetivo_tardes": resumen.objetivo_tardes,
+            "recuperar": resumen.recuperar,
+        },
+    });
+    if let Evento::Fichaje { fecha, celda, hora } = evento {
+        carga["fichaje"] = json!({
+            "fecha": fecha.to_string(),
+            "nombre": FICHAJE_NAMES[celda / 5],
+            "hora": hora,
+        });
+    }
+    carga
+}
+
+/// Sigue el saldo y las tardes para avisar una sola vez de cada cambio.
+#[derive(Debug, Default)]
+pub struct Vigilante {
+    /// Signo del último saldo distinto de cero; `None` hasta verlo.
+    positivo: Option<bool>,
+    /// Semana de la que ya se ha avisado de las tardes.
+    avisado: Option<NaiveDate>,
+}
+
+impl Vigilante {
+    pub fn revisar(&mut self, datos: &Datos, ahora: NaiveDateTime) -> Vec<Evento> {
+        let estado = estado::estado(datos, ahora);
+        let mut eventos = Vec::new();
+
+        // Un saldo justo a cero (la semana sin empezar) no tiene signo.
+        if estado.saldo != 0 {
+            let positivo = estado.saldo > 0;
+            if self.positivo.is_some_and(|antes| antes != positivo) {
+                eventos.push(Evento::Saldo(positivo));
+            }
+            self.positivo = Some(positivo);
+        }
+
+        // Último día laborable de la semana: los que quedan detrás son ausencias.
+        let lunes = datos.lunes.unwrap_or_else(crate::lunes_actual);
+        let objetivo = calculo::segundos(&datos.config[1].cell);
+        let ultimo = estado.dia.is_some_and(|dia| {
+            (dia + 1..5).all(|d| {
+                datos
+                    .ausencias
+                    .contains_key(&(lunes + Dias::days(d as i64)))
+            })
+        });
+        // Se avisa al salir, después de la mañana: sin un tramo de tarde abierto no va a llegar.
+        let fuera = estado
+            .dia
+            .is_some_and(|dia| !estado.dentro && !datos.fichajes[dia + 5].cell.trim().is_empty());
+        if ultimo
+            && fuera
+            && objetivo > 0
+            && estado.tardes < objetivo
+            && self.avisado != Some(lunes)
+        {
+            eventos.push(Evento::TardesEnRiesgo);
+            self.avisado = Some(lunes);
+        }
+
+        eventos
+    }
+}
+
+/// `http://host[:puerto]/ruta`, con las direcciones IPv6 entre corchetes: `http://[::1]:8080/`.
+/// El host se devuelve sin corchetes.
+fn partes(url: &str) -> io::Result<(String, u16, String)> {
+    let invalida = || io::Error::new(io::ErrorKind::InvalidInput, "URL no v\u{e1}lida (http://)");
+    let resto = url.trim().strip_prefix("http://").ok_or_else(invalida)?;
+    let (servidor, ruta) = resto.split_at(resto.find('/').unwrap_or(resto.len()));
+    let (host, puerto) = match servidor.strip_prefix('[') {
+        Some(ipv6) => ipv6.split_once(']').ok_or_else(invalida)?,
+        None => servidor.rsplit_once(':').unwrap_or((servidor, "")),
+    };
+    let puerto = match puerto {
+        "" => 80,
+        puerto => puerto
+            .strip_prefix(':')
+            .unwrap_or(puerto)
+            .parse()
+            .map_err(|_| invalida())?,
+    };
+    if host.is_empty() {
+        return Err(invalida());
+    }
+    Ok((
+        host.to_owned(),
+        puerto,
+        if ruta.is_empty() { "/" } else { ruta }.to_owned(),
+    ))
+}
+
+/// Comprueba la URL sin enviar nada.
+pub fn validar(url: &str) -> io::Result<()> {
+    partes(url).map(|_| ())
+}
+
+/// Un POST; `Ok` si el servicio contesta 2xx.
+pub fn enviar(url: &str, carga: &Value) -> io::Result<()> {
+    let (host, puerto, ruta) = partes(url)?;
+    let direccion = (host.as_str(), puerto)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, host.clone()))?;
+
+    let host = if host.contains(':') {
+        format!("[{host}]")
+    } else {
+        host
+    };
+
+    let mut conexion = TcpStream::connect_timeout(&direccion, Duration::from_secs(5))?;
+    conexion.set_read_timeout(Some(Duration::from_secs(10)))?;
+
+    let cuerpo = carga.to_string();
+    write!(
+        conexion,
+        "POST {} HTTP/1.1\r\nHost: {}:{}\r\nUser-Agent: horario\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        ruta,
+        host,
+        puerto,
+        cuerpo.len(),
+        cuerpo
+    )?;
+
+    let mut linea = String::new();
+    BufReader::new(&conexion).read_line(&mut linea)?;
+    match linea.split_whitespace().nth(1) {
+        Some(codigo) if codigo.starts_with('2') => Ok(()),
+        codigo => Err(io::Error::other(format!(
+            "respuesta {}",
+            codigo.unwrap_or("vac\u{ed}a")
+        ))),
+    }
+}
+
+/// Envía la prueba en otro hilo, sin reintentos, y llama a `despertar` con el resultado en el
+/// canal para que la ventana no se quede esperando al servicio.
+pub fn probar(
+    url: String,
+    carga: Value,
+    despertar: impl FnOnce() + Send + 'static,
+) -> Receiver<io::Result<()>> {
+    let (resultado, recibir) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = resultado.send(enviar(&url, &carga));
+        despertar();
+    });
+    recibir
+}
+
+/// Hilo que envía los avisos en orden, con reintentos, sin bloquear a quien los genera.
+#[derive(Debug)]
+pub struct Enviador {
+    cola: Option<Sender<(String, Value)>>,
+    hilo: Option<JoinHandle<()>>,
+}
+
+impl Enviador {
+    pub fn iniciar() -> Self {
+        let (cola, recibir) = mpsc::channel::<(String, Value)>();
+        let hilo = std::thread::spawn(move || {
+            for (url, carga) in recibir {
+                for intento in 0..INTENTOS {
+                    match enviar(&url, &carga) {
+                        Ok(()) => break,
+                        Err(err) if intento + 1 == INTENTOS => eprintln!("webhook {url}: {err}"),
+                        Err(_) => std::thread::sleep(Duration::from_secs(1 << intento)),
+                    }
+                }
+            }
+        });
+
+        Self {
+            cola: Some(cola),
+            hilo: Some(hilo),
+        }
+    }
+
+    /// Pone en cola los eventos que estén activos en la configuración.
+    pub fn avisar(&self, datos: &Datos, eventos: &[Evento], ahora: NaiveDateTime) {
+        let config = &datos.webhook;
+        if config.url.trim().is_empty() {
+            return;
+        }
+        for evento in eventos.iter().filter(|e| e.activo(config)) {
+            if let Some(cola) = &self.cola {
+                let _ = cola.send((config.url.clone(), carga(evento, datos, ahora)));
+            }
+        }
+    }
+
+    /// Espera a que salga lo que está en cola, como mucho `plazo`; para procesos que terminan
+    /// enseguida. Lo que siga reintentándose al acabar el plazo se pierde.
+    pub fn terminar(mut self, plazo: Duration) {
+        self.cola = None;
+        let Some(hilo) = self.hilo.take() else {
+            return;
+        };
+
+        let limite = Instant::now() + plazo;
+        while !hilo.is_finished() && Instant::now() < limite {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        if hilo.is_finished() {
+            let _ = hilo.join();
+        } else {
+            eprintln!("webhook: sin respuesta, no se reintenta");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn la_carga_lleva_el_momento_del_evento() {
+        let ahora = NaiveDate::from_ymd_opt(2026, 3, 10)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap();
+        let carga = carga(&Evento::Prueba, &Datos::default(), ahora);
+        let momento = carga["momento"].as_str().unwrap();
+
+        assert!(momento.starts_with("2026-03-10T10:00:00"), "{momento}");
+    }
+}